@@ -1,22 +1,79 @@
 use argon2::Error as ArgonError;
+use serde::Serialize;
+use serde_json::json;
 use std::fmt::Formatter;
+use uuid::Uuid;
 use warp::reject::Reject;
-use warp::{filters::body::BodyDeserializeError, http::StatusCode, Rejection, Reply}; // Bring the Filter trait to scope for using `map`
+use warp::{
+    filters::body::BodyDeserializeError,
+    http::{header::HeaderValue, StatusCode},
+    reject::{
+        InvalidHeader, InvalidQuery, LengthRequired, MethodNotAllowed, MissingHeader,
+        PayloadTooLarge, UnsupportedMediaType,
+    },
+    Rejection, Reply,
+}; // Bring the Filter trait to scope for using `map`
 
 use tracing::{event, Level};
-
 #[derive(Debug)]
 pub enum Error {
     ParseError(std::num::ParseIntError),
     MissingParameters,
+    InvalidParameter(String),
     RangeError,
     QuestionNotFound,
+    AnswerNotFound,
+    RevisionNotFound,
+    AnswerNotInQuestion,
+    AccountNotFound,
+    ApiKeyNotFound,
     DatabaseQueryError(sqlx::Error),
     WrongPassword,
     ArgonLibraryError(ArgonError),
     CannotDecrptToken,
+    MissingToken,
+    MalformedToken,
+    TokenExpired,
+    TokenNotYetValid,
+    InvalidApiKey,
+    SessionRevoked,
+    InvalidRefreshToken,
+    InvalidResetToken,
+    InvalidVerificationToken,
+    EmailNotVerified,
     Unauthorized,
+    InsufficientRole,
+    InsufficientScope,
     MigrationError(sqlx::migrate::MigrateError),
+    MailerError(String),
+    OidcNotConfigured,
+    /// Discovery, key or token request to the identity provider failed
+    OidcProviderError(String),
+    InvalidOidcLogin,
+    InvalidIdToken,
+    /// The email of a new identity belongs to an account that was never verified
+    OidcAccountConflict,
+    ReauthenticationRequired,
+    /// The moderation action doesn't apply to the current state of the content
+    InvalidModerationAction,
+    /// `If-Match` of a write names a version that is no longer current
+    PreconditionFailed,
+    /// Undelete asked for content that isn't deleted
+    NotDeleted,
+    /// Grace period in which the author may undelete their content is over
+    UndeleteWindowExpired,
+    /// Name of the field whose text the content filter turned down
+    ContentRejected(String),
+    /// Content filter service failed and the failure policy is closed
+    ContentFilterUnavailable(String),
+    /// Seconds until the next login attempt is accepted
+    TooManyLoginAttempts(u64),
+    /// Rate limit bucket of the client is empty, `reset` and `retry_after` are in seconds
+    RateLimited {
+        limit: u32,
+        reset: u64,
+        retry_after: u64,
+    },
 }
 
 impl std::fmt::Display for Error {
@@ -26,8 +83,16 @@ impl std::fmt::Display for Error {
                 write!(f, "Cannot parse parameter: {}", err)
             }
             Error::MissingParameters => write!(f, "Missing parameter"),
+            Error::InvalidParameter(ref name) => write!(f, "Invalid parameter: {}", name),
             Error::RangeError => write!(f, "Range error"),
             Error::QuestionNotFound => write!(f, "Question Not Found"),
+            Error::AnswerNotFound => write!(f, "Answer Not Found"),
+            Error::RevisionNotFound => write!(f, "Revision Not Found"),
+            Error::AccountNotFound => write!(f, "Account Not Found"),
+            Error::ApiKeyNotFound => write!(f, "API Key Not Found"),
+            Error::AnswerNotInQuestion => {
+                write!(f, "Answer does not belong to the question")
+            }
             Error::DatabaseQueryError(_) => {
                 write!(f, "Query couldn't be executed")
             }
@@ -40,12 +105,100 @@ impl std::fmt::Display for Error {
             Error::CannotDecrptToken => {
                 write!(f, "Cannot decrypt token")
             }
+            Error::MissingToken => {
+                write!(f, "Authorization header with a bearer token is required")
+            }
+            Error::MalformedToken => {
+                write!(
+                    f,
+                    "Authorization header must have the form `Bearer <token>`"
+                )
+            }
+            Error::TokenExpired => {
+                write!(f, "Token has expired")
+            }
+            Error::TokenNotYetValid => {
+                write!(f, "Token is not valid yet")
+            }
+            Error::InvalidApiKey => {
+                write!(f, "API key is invalid or revoked")
+            }
+            Error::SessionRevoked => {
+                write!(f, "Session has been logged out")
+            }
+            Error::InvalidRefreshToken => {
+                write!(f, "Refresh token is invalid or expired")
+            }
             Error::Unauthorized => {
                 write!(f, "Request is unauthorized")
             }
+            Error::InsufficientRole => {
+                write!(f, "Account role does not allow this request")
+            }
+            Error::InsufficientScope => {
+                write!(f, "API key scopes do not allow this request")
+            }
             Error::MigrationError(_) => {
                 write!(f, "Error when doing migration")
             }
+            Error::InvalidResetToken => {
+                write!(f, "Password reset token is invalid or expired")
+            }
+            Error::InvalidVerificationToken => {
+                write!(f, "Email verification token is invalid or expired")
+            }
+            Error::EmailNotVerified => {
+                write!(f, "Email address has not been verified")
+            }
+            Error::MailerError(_) => {
+                write!(f, "Email couldn't be sent")
+            }
+            Error::OidcNotConfigured => {
+                write!(f, "Login with an identity provider is not configured")
+            }
+            Error::OidcProviderError(_) => {
+                write!(f, "Identity provider couldn't be reached")
+            }
+            Error::InvalidOidcLogin => {
+                write!(f, "Login with the identity provider is invalid or expired")
+            }
+            Error::InvalidIdToken => {
+                write!(f, "ID token of the identity provider is invalid")
+            }
+            Error::OidcAccountConflict => {
+                write!(
+                    f,
+                    "An account with this email address exists but was never verified"
+                )
+            }
+            Error::ReauthenticationRequired => {
+                write!(f, "Log in again to confirm this request")
+            }
+            Error::InvalidModerationAction => {
+                write!(
+                    f,
+                    "Action does not apply to the content in its current state"
+                )
+            }
+            Error::PreconditionFailed => {
+                write!(f, "Resource was changed since it was read")
+            }
+            Error::NotDeleted => write!(f, "Content is not deleted"),
+            Error::UndeleteWindowExpired => {
+                write!(f, "Content can no longer be undeleted")
+            }
+            Error::ContentRejected(ref field) => {
+                write!(f, "Inappropriate language in {}", field)
+            }
+            Error::ContentFilterUnavailable(_) => {
+                write!(f, "Content couldn't be checked, try again later")
+            }
+            Error::TooManyLoginAttempts(_) => {
+                write!(f, "Too many failed login attempts")
+            }
+            Error::RateLimited { .. } => {
+                write!(f, "Too many requests")
+            }
         }
     }
 }
@@ -53,57 +206,317 @@ impl std::fmt::Display for Error {
 // You must implement the Reject Trait, to match the Result of Error type which implemented Rejection
 impl Reject for Error {}
 
-const DUPLICATE_KEY: u32 = 23505;
+const DUPLICATE_KEY: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
 
-pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
-    if let Some(crate::Error::DatabaseQueryError(e)) = r.find() {
-        event!(Level::ERROR, "Database query error");
-        match e {
-            sqlx::Error::Database(err) => {
-                if err.code().unwrap().parse::<u32>().unwrap() == DUPLICATE_KEY {
-                    Ok(warp::reply::with_status(
-                        "Account already exists".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
-                } else {
-                    Ok(warp::reply::with_status(
-                        "Cannot update data".to_string(),
-                        StatusCode::UNPROCESSABLE_ENTITY,
-                    ))
-                }
-            }
-            _ => Ok(warp::reply::with_status(
-                "Cannot update data".to_string(),
-                StatusCode::UNPROCESSABLE_ENTITY,
-            )),
+impl Error {
+    /// HTTP status the error is answered with
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::ParseError(_)
+            | Error::MissingParameters
+            | Error::InvalidParameter(_)
+            | Error::RangeError
+            | Error::InvalidResetToken
+            | Error::InvalidVerificationToken
+            | Error::InvalidOidcLogin => StatusCode::BAD_REQUEST,
+            Error::QuestionNotFound
+            | Error::AnswerNotFound
+            | Error::RevisionNotFound
+            | Error::AccountNotFound
+            | Error::ApiKeyNotFound
+            | Error::OidcNotConfigured => StatusCode::NOT_FOUND,
+            Error::OidcAccountConflict | Error::InvalidModerationAction | Error::NotDeleted => {
+                StatusCode::CONFLICT
+            }
+            Error::UndeleteWindowExpired => StatusCode::GONE,
+            Error::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Error::AnswerNotInQuestion | Error::ContentRejected(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            // Lookups of a single row by id that doesn't exist
+            Error::DatabaseQueryError(sqlx::Error::RowNotFound) => StatusCode::NOT_FOUND,
+            Error::DatabaseQueryError(e) => match database_error_code(e).as_deref() {
+                Some(DUPLICATE_KEY) => StatusCode::CONFLICT,
+                Some(FOREIGN_KEY_VIOLATION) => StatusCode::UNPROCESSABLE_ENTITY,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Error::WrongPassword
+            | Error::CannotDecrptToken
+            | Error::MissingToken
+            | Error::MalformedToken
+            | Error::TokenExpired
+            | Error::TokenNotYetValid
+            | Error::InvalidApiKey
+            | Error::SessionRevoked
+            | Error::InvalidRefreshToken
+            | Error::InvalidIdToken
+            | Error::ReauthenticationRequired => StatusCode::UNAUTHORIZED,
+            Error::Unauthorized
+            | Error::EmailNotVerified
+            | Error::InsufficientRole
+            | Error::InsufficientScope => StatusCode::FORBIDDEN,
+            Error::TooManyLoginAttempts(_) | Error::RateLimited { .. } => {
+                StatusCode::TOO_MANY_REQUESTS
+            }
+            Error::ArgonLibraryError(_) | Error::MigrationError(_) | Error::MailerError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            Error::OidcProviderError(_) => StatusCode::BAD_GATEWAY,
+            Error::ContentFilterUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
         }
-    } else if let Some(error) = r.find::<warp::cors::CorsForbidden>() {
-        Ok(warp::reply::with_status(
-            error.to_string(),
-            StatusCode::FORBIDDEN,
+    }
+
+    /// Stable, machine readable identifier of the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::ParseError(_) => "parse_error",
+            Error::MissingParameters => "missing_parameters",
+            Error::InvalidParameter(_) => "invalid_parameter",
+            Error::RangeError => "range_error",
+            Error::QuestionNotFound => "question_not_found",
+            Error::AnswerNotFound => "answer_not_found",
+            Error::RevisionNotFound => "revision_not_found",
+            Error::ApiKeyNotFound => "api_key_not_found",
+            Error::AccountNotFound => "account_not_found",
+            Error::AnswerNotInQuestion => "answer_not_in_question",
+            Error::DatabaseQueryError(sqlx::Error::RowNotFound) => "not_found",
+            Error::DatabaseQueryError(e) => match database_error_code(e).as_deref() {
+                Some(DUPLICATE_KEY) => "already_exists",
+                Some(FOREIGN_KEY_VIOLATION) => "invalid_reference",
+                _ => "database_error",
+            },
+            Error::WrongPassword => "wrong_credentials",
+            Error::ArgonLibraryError(_) => "password_verification_failed",
+            Error::CannotDecrptToken => "invalid_token",
+            Error::MissingToken => "missing_token",
+            Error::MalformedToken => "malformed_token",
+            Error::TokenExpired => "token_expired",
+            Error::TokenNotYetValid => "token_not_yet_valid",
+            Error::InvalidApiKey => "invalid_api_key",
+            Error::SessionRevoked => "session_revoked",
+            Error::InvalidRefreshToken => "invalid_refresh_token",
+            Error::Unauthorized => "forbidden",
+            Error::InsufficientRole => "insufficient_role",
+            Error::InsufficientScope => "insufficient_scope",
+            Error::MigrationError(_) => "migration_error",
+            Error::InvalidResetToken => "invalid_reset_token",
+            Error::InvalidVerificationToken => "invalid_verification_token",
+            Error::EmailNotVerified => "email_not_verified",
+            Error::MailerError(_) => "mailer_error",
+            Error::OidcNotConfigured => "oidc_not_configured",
+            Error::OidcProviderError(_) => "oidc_provider_error",
+            Error::InvalidOidcLogin => "invalid_oidc_login",
+            Error::InvalidIdToken => "invalid_id_token",
+            Error::OidcAccountConflict => "oidc_account_conflict",
+            Error::ReauthenticationRequired => "reauthentication_required",
+            Error::InvalidModerationAction => "invalid_moderation_action",
+            Error::PreconditionFailed => "precondition_failed",
+            Error::NotDeleted => "not_deleted",
+            Error::UndeleteWindowExpired => "undelete_window_expired",
+            Error::ContentRejected(_) => "content_rejected",
+            Error::ContentFilterUnavailable(_) => "content_filter_unavailable",
+            Error::TooManyLoginAttempts(_) => "too_many_login_attempts",
+            Error::RateLimited { .. } => "rate_limited",
+        }
+    }
+
+    /// Message sent to clients, internal failures are not spelled out
+    fn message(&self) -> String {
+        match self {
+            Error::DatabaseQueryError(sqlx::Error::RowNotFound) => "Resource not found".to_string(),
+            Error::DatabaseQueryError(e) => match database_error_code(e).as_deref() {
+                Some(DUPLICATE_KEY) => "Resource already exists".to_string(),
+                Some(FOREIGN_KEY_VIOLATION) => "Referenced resource does not exist".to_string(),
+                _ => self.to_string(),
+            },
+            Error::WrongPassword => "Wrong E-Mail/Password combination".to_string(),
+            Error::Unauthorized => "No permission to change underlying resource".to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    fn details(&self) -> Option<serde_json::Value> {
+        match self {
+            Error::InvalidParameter(name) | Error::ContentRejected(name) => {
+                Some(json!({ "parameter": name }))
+            }
+            Error::TooManyLoginAttempts(seconds) => Some(json!({ "retry_after": seconds })),
+            Error::RateLimited { retry_after, .. } => Some(json!({ "retry_after": retry_after })),
+            Error::DatabaseQueryError(sqlx::Error::Database(e)) => e
+                .constraint()
+                .map(|constraint| json!({ "constraint": constraint })),
+            _ => None,
+        }
+    }
+
+    /// Headers sent along with the error response
+    fn headers(&self) -> Vec<(&'static str, HeaderValue)> {
+        match self {
+            // RFC 6750 leaves out the error when no credentials were sent at all
+            Error::MissingToken => vec![("www-authenticate", HeaderValue::from_static("Bearer"))],
+            Error::MalformedToken => {
+                vec![("www-authenticate", self.bearer_challenge("invalid_request"))]
+            }
+            Error::CannotDecrptToken
+            | Error::TokenExpired
+            | Error::TokenNotYetValid
+            | Error::InvalidApiKey
+            | Error::SessionRevoked => {
+                vec![("www-authenticate", self.bearer_challenge("invalid_token"))]
+            }
+            Error::InsufficientScope => {
+                vec![(
+                    "www-authenticate",
+                    self.bearer_challenge("insufficient_scope"),
+                )]
+            }
+            Error::TooManyLoginAttempts(seconds) => vec![("retry-after", (*seconds).into())],
+            Error::RateLimited {
+                limit,
+                reset,
+                retry_after,
+            } => vec![
+                ("retry-after", (*retry_after).into()),
+                ("x-ratelimit-limit", (*limit).into()),
+                ("x-ratelimit-remaining", 0.into()),
+                ("x-ratelimit-reset", (*reset).into()),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// `WWW-Authenticate` value of a rejected bearer token
+    fn bearer_challenge(&self, error: &str) -> HeaderValue {
+        let description = self.to_string().replace(['"', '\\'], "'");
+        HeaderValue::from_str(&format!(
+            "Bearer error=\"{}\", error_description=\"{}\"",
+            error, description
         ))
-    } else if let Some(error) = r.find::<BodyDeserializeError>() {
-        Ok(warp::reply::with_status(
-            error.to_string(),
+        .unwrap_or_else(|_| HeaderValue::from_static("Bearer"))
+    }
+}
+
+fn database_error_code(e: &sqlx::Error) -> Option<String> {
+    match e {
+        sqlx::Error::Database(err) => err.code().map(|code| code.into_owned()),
+        _ => None,
+    }
+}
+
+/// Body of every error response
+#[derive(Serialize, Debug)]
+pub struct ErrorResponse {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    pub request_id: String,
+}
+
+pub async fn return_error(r: Rejection) -> Result<impl Reply, Rejection> {
+    let (status, code, message, details) = if let Some(e) = r.find::<Error>() {
+        (e.status(), e.code(), e.message(), e.details())
+    } else if let Some(e) = r.find::<warp::cors::CorsForbidden>() {
+        (StatusCode::FORBIDDEN, "cors_forbidden", e.to_string(), None)
+    } else if let Some(e) = r.find::<BodyDeserializeError>() {
+        (
             StatusCode::UNPROCESSABLE_ENTITY,
-        ))
-    } else if let Some(crate::Error::WrongPassword) = r.find() {
-        event!(Level::ERROR, "Enter Wrong password");
-        Ok(warp::reply::with_status(
-            "Wrong E-Mail/Password combination".to_string(),
-            StatusCode::UNAUTHORIZED,
-        ))
-    } else if let Some(crate::Error::Unauthorized) = r.find() {
-        event!(Level::ERROR, "Not matching account id");
-        Ok(warp::reply::with_status(
-            "No permission to change underlying resource".to_string(),
-            StatusCode::UNAUTHORIZED,
-        ))
-    } else {
-        println! {"{:?}", r};
-        Ok(warp::reply::with_status(
-            "Route not found".to_string(),
+            "invalid_body",
+            "Request body is invalid".to_string(),
+            Some(json!({ "reason": e.to_string() })),
+        )
+    } else if let Some(e) = r.find::<InvalidQuery>() {
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid_query",
+            e.to_string(),
+            None,
+        )
+    } else if let Some(e) = r.find::<MissingHeader>() {
+        let status = if e.name().eq_ignore_ascii_case("authorization") {
+            StatusCode::UNAUTHORIZED
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        (
+            status,
+            "missing_header",
+            e.to_string(),
+            Some(json!({ "header": e.name() })),
+        )
+    } else if let Some(e) = r.find::<InvalidHeader>() {
+        (
+            StatusCode::BAD_REQUEST,
+            "invalid_header",
+            e.to_string(),
+            Some(json!({ "header": e.name() })),
+        )
+    } else if let Some(e) = r.find::<PayloadTooLarge>() {
+        (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "payload_too_large",
+            e.to_string(),
+            None,
+        )
+    } else if let Some(e) = r.find::<LengthRequired>() {
+        (
+            StatusCode::LENGTH_REQUIRED,
+            "length_required",
+            e.to_string(),
+            None,
+        )
+    } else if let Some(e) = r.find::<UnsupportedMediaType>() {
+        (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "unsupported_media_type",
+            e.to_string(),
+            None,
+        )
+    } else if let Some(e) = r.find::<MethodNotAllowed>() {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "method_not_allowed",
+            e.to_string(),
+            None,
+        )
+    } else if r.is_not_found() {
+        (
             StatusCode::NOT_FOUND,
-        ))
+            "not_found",
+            "Route not found".to_string(),
+            None,
+        )
+    } else {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "internal_error",
+            "Unhandled rejection".to_string(),
+            None,
+        )
+    };
+
+    let request_id = Uuid::new_v4().to_string();
+    if status.is_server_error() {
+        event!(Level::ERROR, request_id = %request_id, code, rejection = ?r, "{}", message);
+    } else {
+        event!(Level::WARN, request_id = %request_id, code, "{}", message);
     }
+
+    let body = ErrorResponse {
+        code,
+        message,
+        details,
+        request_id: request_id.clone(),
+    };
+    let mut response = warp::reply::with_status(warp::reply::json(&body), status).into_response();
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert("x-request-id", value);
+    }
+    if let Some(e) = r.find::<Error>() {
+        for (name, value) in e.headers() {
+            response.headers_mut().insert(name, value);
+        }
+    }
+
+    Ok(response)
 }
@@ -0,0 +1,2 @@
+ALTER TABLE answers
+RENAME COLUMN question_id TO corresponding_question;
@@ -0,0 +1,2 @@
+ALTER TABLE answers
+RENAME COLUMN corresponding_question TO question_id;
@@ -0,0 +1,21 @@
+ALTER TABLE questions
+ADD COLUMN score integer NOT NULL DEFAULT 0;
+
+ALTER TABLE answers
+ADD COLUMN score integer NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS question_votes (
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  account_id integer NOT NULL,
+  value smallint NOT NULL CHECK (value IN (-1, 1)),
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (question_id, account_id)
+);
+
+CREATE TABLE IF NOT EXISTS answer_votes (
+  answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+  account_id integer NOT NULL,
+  value smallint NOT NULL CHECK (value IN (-1, 1)),
+  created_on TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (answer_id, account_id)
+);
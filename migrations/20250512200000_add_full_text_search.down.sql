@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS answers_search_vector_idx;
+DROP INDEX IF EXISTS questions_search_vector_idx;
+
+ALTER TABLE answers
+DROP COLUMN search_vector;
+
+ALTER TABLE questions
+DROP COLUMN search_vector;
@@ -0,0 +1,4 @@
+ALTER TABLE accounts
+DROP COLUMN sessions_revoked_before;
+
+DROP TABLE IF EXISTS sessions;
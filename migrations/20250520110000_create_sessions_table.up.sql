@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS sessions (
+  id TEXT PRIMARY KEY,
+  account_id integer NOT NULL,
+  refresh_token_hash VARCHAR(255) NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_on TIMESTAMPTZ NOT NULL,
+  revoked_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS sessions_account_id_idx ON sessions (account_id);
+
+ALTER TABLE accounts
+ADD COLUMN sessions_revoked_before TIMESTAMPTZ;
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS password_resets (
+  id TEXT PRIMARY KEY,
+  account_id integer NOT NULL,
+  token_hash VARCHAR(255) NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_on TIMESTAMPTZ NOT NULL,
+  used_on TIMESTAMPTZ
+);
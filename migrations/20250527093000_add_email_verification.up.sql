@@ -0,0 +1,14 @@
+ALTER TABLE accounts
+ADD COLUMN email_verified_on TIMESTAMPTZ;
+
+-- Accounts registered before verification existed stay usable
+UPDATE accounts SET email_verified_on = NOW();
+
+CREATE TABLE IF NOT EXISTS email_verifications (
+  id TEXT PRIMARY KEY,
+  account_id integer NOT NULL,
+  token_hash VARCHAR(255) NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_on TIMESTAMPTZ NOT NULL,
+  used_on TIMESTAMPTZ
+);
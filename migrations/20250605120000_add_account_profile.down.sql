@@ -0,0 +1,5 @@
+ALTER TABLE accounts
+DROP COLUMN created_on,
+DROP COLUMN avatar_url,
+DROP COLUMN bio,
+DROP COLUMN display_name;
@@ -0,0 +1,5 @@
+ALTER TABLE accounts
+ADD COLUMN display_name VARCHAR(50),
+ADD COLUMN bio TEXT,
+ADD COLUMN avatar_url TEXT,
+ADD COLUMN created_on TIMESTAMPTZ NOT NULL DEFAULT NOW();
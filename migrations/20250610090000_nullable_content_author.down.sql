@@ -0,0 +1,11 @@
+DELETE FROM answers
+WHERE account_id IS NULL OR question_id IN (SELECT id FROM questions WHERE account_id IS NULL);
+DELETE FROM questions WHERE account_id IS NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id SET NOT NULL,
+ALTER COLUMN account_id SET DEFAULT nextval('answers_account_id_seq');
+
+ALTER TABLE questions
+ALTER COLUMN account_id SET NOT NULL,
+ALTER COLUMN account_id SET DEFAULT nextval('questions_account_id_seq');
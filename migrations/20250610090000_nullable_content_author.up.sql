@@ -0,0 +1,8 @@
+-- Content of deleted accounts can be kept without an author
+ALTER TABLE questions
+ALTER COLUMN account_id DROP DEFAULT,
+ALTER COLUMN account_id DROP NOT NULL;
+
+ALTER TABLE answers
+ALTER COLUMN account_id DROP DEFAULT,
+ALTER COLUMN account_id DROP NOT NULL;
@@ -0,0 +1,9 @@
+-- Failed logins per email address and per client IP, shared by every replica
+CREATE TABLE IF NOT EXISTS login_attempts (
+  kind TEXT NOT NULL CHECK (kind IN ('email', 'ip')),
+  key TEXT NOT NULL,
+  failures integer NOT NULL DEFAULT 0,
+  last_failure_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  locked_until TIMESTAMPTZ,
+  PRIMARY KEY (kind, key)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS question_bookmarks (
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  account_id integer NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (question_id, account_id)
+);
+
+CREATE INDEX IF NOT EXISTS question_bookmarks_account_id_idx ON question_bookmarks (account_id);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS api_keys (
+  id TEXT PRIMARY KEY,
+  account_id integer NOT NULL,
+  name TEXT NOT NULL,
+  scopes TEXT[] NOT NULL,
+  key_hash VARCHAR(255) NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  last_used_on TIMESTAMPTZ,
+  revoked_on TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS api_keys_account_id_idx ON api_keys (account_id);
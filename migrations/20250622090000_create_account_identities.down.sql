@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS oidc_logins;
+DROP TABLE IF EXISTS account_identities;
+
+-- Password-less accounts can't log in anymore, they keep an empty password hash
+UPDATE accounts SET password = '' WHERE password IS NULL;
+
+ALTER TABLE accounts
+ALTER COLUMN password SET NOT NULL;
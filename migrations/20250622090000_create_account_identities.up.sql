@@ -0,0 +1,21 @@
+-- Accounts created through an identity provider have no password
+ALTER TABLE accounts
+ALTER COLUMN password DROP NOT NULL;
+
+CREATE TABLE IF NOT EXISTS account_identities (
+  issuer TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  account_id integer NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS account_identities_account_id_idx ON account_identities (account_id);
+
+CREATE TABLE IF NOT EXISTS oidc_logins (
+  state TEXT PRIMARY KEY,
+  nonce TEXT NOT NULL,
+  code_verifier TEXT NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  expires_on TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS moderation_actions;
+DROP TABLE IF EXISTS flags;
+
+ALTER TABLE answers
+DROP COLUMN IF EXISTS moderation_state;
+
+ALTER TABLE questions
+DROP COLUMN IF EXISTS moderation_state;
@@ -0,0 +1,34 @@
+-- 'visible', 'queued' once flagged often enough, or 'removed' by a moderator
+ALTER TABLE questions
+ADD COLUMN moderation_state TEXT NOT NULL DEFAULT 'visible';
+
+ALTER TABLE answers
+ADD COLUMN moderation_state TEXT NOT NULL DEFAULT 'visible';
+
+CREATE TABLE IF NOT EXISTS flags (
+  id serial PRIMARY KEY,
+  target_kind TEXT NOT NULL,
+  target_id integer NOT NULL,
+  account_id integer NOT NULL,
+  reason TEXT NOT NULL,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  resolved_on TIMESTAMPTZ
+);
+
+-- An account can only have one open flag on the same question or answer
+CREATE UNIQUE INDEX IF NOT EXISTS flags_open_idx ON flags (target_kind, target_id, account_id)
+WHERE resolved_on IS NULL;
+
+CREATE INDEX IF NOT EXISTS flags_account_id_idx ON flags (account_id);
+
+CREATE TABLE IF NOT EXISTS moderation_actions (
+  id serial PRIMARY KEY,
+  moderator_id integer NOT NULL,
+  action TEXT NOT NULL,
+  target_kind TEXT NOT NULL,
+  target_id integer NOT NULL,
+  note TEXT,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS moderation_actions_target_idx ON moderation_actions (target_kind, target_id);
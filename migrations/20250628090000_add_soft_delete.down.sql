@@ -0,0 +1,16 @@
+-- Soft deleted content would show up again, delete it for good
+DELETE FROM answers
+WHERE deleted_at IS NOT NULL
+OR question_id IN (SELECT id FROM questions WHERE deleted_at IS NOT NULL);
+DELETE FROM questions WHERE deleted_at IS NOT NULL;
+
+DROP INDEX IF EXISTS answers_deleted_at_idx;
+DROP INDEX IF EXISTS questions_deleted_at_idx;
+
+ALTER TABLE answers
+DROP COLUMN IF EXISTS deleted_at,
+DROP COLUMN IF EXISTS deleted_by;
+
+ALTER TABLE questions
+DROP COLUMN IF EXISTS deleted_at,
+DROP COLUMN IF EXISTS deleted_by;
@@ -0,0 +1,11 @@
+ALTER TABLE questions
+ADD COLUMN deleted_at TIMESTAMPTZ,
+ADD COLUMN deleted_by integer;
+
+ALTER TABLE answers
+ADD COLUMN deleted_at TIMESTAMPTZ,
+ADD COLUMN deleted_by integer;
+
+-- The purge job looks for content deleted before its retention period
+CREATE INDEX IF NOT EXISTS questions_deleted_at_idx ON questions (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS answers_deleted_at_idx ON answers (deleted_at) WHERE deleted_at IS NOT NULL;
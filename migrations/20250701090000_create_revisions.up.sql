@@ -0,0 +1,30 @@
+-- Every edit of a question or answer, including the first version, numbered from 1
+CREATE TABLE IF NOT EXISTS question_revisions (
+  question_id integer NOT NULL REFERENCES questions ON DELETE CASCADE,
+  revision integer NOT NULL,
+  title VARCHAR (255) NOT NULL,
+  content TEXT NOT NULL,
+  tags TEXT [],
+  editor_id integer,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (question_id, revision)
+);
+
+CREATE TABLE IF NOT EXISTS answer_revisions (
+  answer_id integer NOT NULL REFERENCES answers ON DELETE CASCADE,
+  revision integer NOT NULL,
+  content TEXT NOT NULL,
+  editor_id integer,
+  created_on TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (answer_id, revision)
+);
+
+CREATE INDEX IF NOT EXISTS question_revisions_editor_id_idx ON question_revisions (editor_id);
+CREATE INDEX IF NOT EXISTS answer_revisions_editor_id_idx ON answer_revisions (editor_id);
+
+-- Existing content starts its history at its current version
+INSERT INTO question_revisions (question_id, revision, title, content, tags, editor_id, created_on)
+SELECT id, 1, title, content, tags, account_id, created_on FROM questions;
+
+INSERT INTO answer_revisions (answer_id, revision, content, editor_id, created_on)
+SELECT id, 1, content, account_id, created_on FROM answers;
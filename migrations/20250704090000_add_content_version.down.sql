@@ -0,0 +1,5 @@
+ALTER TABLE answers
+DROP COLUMN IF EXISTS version;
+
+ALTER TABLE questions
+DROP COLUMN IF EXISTS version;
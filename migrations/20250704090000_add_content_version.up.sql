@@ -0,0 +1,6 @@
+-- Raised by every edit, clients send it back in If-Match to not overwrite each other
+ALTER TABLE questions
+ADD COLUMN version integer NOT NULL DEFAULT 1;
+
+ALTER TABLE answers
+ADD COLUMN version integer NOT NULL DEFAULT 1;
@@ -0,0 +1,3 @@
+ALTER TABLE sessions
+DROP COLUMN IF EXISTS auth_method,
+DROP COLUMN IF EXISTS authenticated_on;
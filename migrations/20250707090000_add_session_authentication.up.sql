@@ -0,0 +1,9 @@
+-- How and when the login behind a session happened, refreshing the session keeps both.
+-- Sessions from before count as logins long ago.
+ALTER TABLE sessions
+ADD COLUMN auth_method TEXT NOT NULL DEFAULT 'password',
+ADD COLUMN authenticated_on TIMESTAMPTZ NOT NULL DEFAULT 'epoch';
+
+ALTER TABLE sessions
+ALTER COLUMN auth_method DROP DEFAULT,
+ALTER COLUMN authenticated_on DROP DEFAULT;
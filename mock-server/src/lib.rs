@@ -0,0 +1,151 @@
+//! Stand-ins for the external services the server talks to, so it can be run locally and
+//! in tests without accounts at them
+//!
+//! `MockServer` replaces the bad words API at `api.apilayer.com/bad_words` used by the `http`
+//! content filter, `oidc` holds an identity provider for the OpenID Connect login.
+
+pub mod oidc;
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use warp::Filter;
+
+/// Words flagged unless the server is started with a list of its own
+pub const DEFAULT_WORDS: &[&str] = &["shit", "crap", "damn"];
+
+/// Makes every request fail with a 500, to try out the failure policy of the filter
+pub const FAIL_MARKER: &str = "__fail__";
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BadWord {
+    pub original: String,
+    pub word: String,
+    pub deviations: i64,
+    pub info: i64,
+    pub start: usize,
+    pub end: usize,
+    #[serde(rename = "replacedLen")]
+    pub replaced_len: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BadWordsResponse {
+    pub content: String,
+    pub bad_words_total: usize,
+    pub bad_words_list: Vec<BadWord>,
+    pub censored_content: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockServer {
+    pub addr: SocketAddr,
+    pub words: Vec<String>,
+    /// Requests served so far, shared by the clones of the server
+    requests: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    pub fn new(addr: SocketAddr, words: Vec<String>) -> Self {
+        MockServer {
+            addr,
+            words: words.into_iter().map(|word| word.to_lowercase()).collect(),
+            requests: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Serve `POST /bad_words` until the process is stopped
+    pub async fn run(self) {
+        let addr = self.addr;
+        warp::serve(self.routes()).run(addr).await
+    }
+
+    /// Serve `POST /bad_words` on a background task and return the address it listens on,
+    /// a free port is picked when the port of `addr` is 0
+    pub fn spawn(&self) -> SocketAddr {
+        let (addr, server) = warp::serve(self.clone().routes()).bind_ephemeral(self.addr);
+        tokio::spawn(server);
+        addr
+    }
+
+    pub fn request_count(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    fn routes(self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let words = self.words;
+        let requests = self.requests;
+
+        warp::path("bad_words")
+            .and(warp::path::end())
+            .and(warp::post())
+            .and(warp::query::<HashMap<String, String>>())
+            .and(warp::body::bytes())
+            .map(
+                move |params: HashMap<String, String>, body: warp::hyper::body::Bytes| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let content = String::from_utf8_lossy(&body).into_owned();
+                    if content.contains(FAIL_MARKER) {
+                        return warp::reply::with_status(
+                            warp::reply::json(
+                                &serde_json::json!({ "message": "failure requested" }),
+                            ),
+                            warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        );
+                    }
+
+                    let censor = params
+                        .get("censor_character")
+                        .and_then(|c| c.chars().next())
+                        .unwrap_or('*');
+                    warp::reply::with_status(
+                        warp::reply::json(&censor_content(&words, content, censor)),
+                        warp::http::StatusCode::OK,
+                    )
+                },
+            )
+    }
+}
+
+/// Mask every listed word found in `content`, matched case-insensitively as whole words
+pub fn censor_content(words: &[String], content: String, censor: char) -> BadWordsResponse {
+    let mut censored_content = String::with_capacity(content.len());
+    let mut bad_words_list = Vec::new();
+    let mut offset = 0;
+
+    while let Some(found) = content[offset..].find(char::is_alphanumeric) {
+        let start = offset + found;
+        let end = content[start..]
+            .find(|c: char| !c.is_alphanumeric())
+            .map_or(content.len(), |len| start + len);
+        let word = &content[start..end];
+        censored_content.push_str(&content[offset..start]);
+
+        if words.contains(&word.to_lowercase()) {
+            let replaced_len = word.chars().count();
+            censored_content.extend(std::iter::repeat_n(censor, replaced_len));
+            bad_words_list.push(BadWord {
+                original: word.to_string(),
+                word: word.to_lowercase(),
+                deviations: 0,
+                info: 2,
+                start,
+                end,
+                replaced_len,
+            });
+        } else {
+            censored_content.push_str(word);
+        }
+        offset = end;
+    }
+    censored_content.push_str(&content[offset..]);
+
+    BadWordsResponse {
+        bad_words_total: bad_words_list.len(),
+        bad_words_list,
+        censored_content,
+        content,
+    }
+}
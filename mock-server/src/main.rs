@@ -0,0 +1,57 @@
+use std::net::SocketAddr;
+
+use mock_server::oidc::MockIdentityProvider;
+use mock_server::{MockServer, DEFAULT_WORDS};
+
+/// Listens on `MOCK_SERVER_PORT` (3030 by default) and flags the comma separated
+/// `MOCK_BAD_WORDS`, or a few default words.
+///
+/// The identity provider listens on `MOCK_OIDC_PORT` (3031 by default) for the client
+/// `MOCK_OIDC_CLIENT_ID` with the secret `MOCK_OIDC_CLIENT_SECRET`.
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            std::env::var("RUST_LOG").unwrap_or_else(|_| "mock_server=info,warp=error".to_owned()),
+        )
+        .init();
+
+    let port = |name: &str, default: u16| {
+        std::env::var(name)
+            .ok()
+            .and_then(|port| port.parse::<u16>().ok())
+            .unwrap_or(default)
+    };
+    let var =
+        |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_owned());
+    let bad_words_port = port("MOCK_SERVER_PORT", 3030);
+    let oidc_port = port("MOCK_OIDC_PORT", 3031);
+
+    let words = match std::env::var("MOCK_BAD_WORDS") {
+        Ok(words) => words
+            .split(',')
+            .map(|word| word.trim().to_string())
+            .collect(),
+        Err(_) => DEFAULT_WORDS.iter().map(|word| word.to_string()).collect(),
+    };
+
+    tracing::event!(
+        tracing::Level::INFO,
+        "Mock bad words API listening on port {}",
+        bad_words_port
+    );
+    tracing::event!(
+        tracing::Level::INFO,
+        "Mock identity provider listening on port {}",
+        oidc_port
+    );
+    tokio::join!(
+        MockServer::new(SocketAddr::from(([127, 0, 0, 1], bad_words_port)), words).run(),
+        MockIdentityProvider::new(
+            SocketAddr::from(([127, 0, 0, 1], oidc_port)),
+            var("MOCK_OIDC_CLIENT_ID", "mock-client"),
+            var("MOCK_OIDC_CLIENT_SECRET", "mock-secret"),
+        )
+        .run(),
+    );
+}
@@ -0,0 +1,308 @@
+//! Stand-in OpenID Connect identity provider, so logins through `OIDC_ISSUER` can be run
+//! locally and in tests. It serves discovery, the signing keys, `/authorize` and `/token`,
+//! and issues RS256 ID tokens for whoever asks to log in.
+
+use ring::digest::{digest, SHA256};
+use ring::rand::SystemRandom;
+use ring::signature::{KeyPair, RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use warp::http::{StatusCode, Uri};
+use warp::Filter;
+
+/// Throwaway key the ID tokens are signed with, never use it for anything else
+const SIGNING_KEY: &str = include_str!("oidc_signing_key.pem");
+
+/// `kid` of the signing key in the JWKS and the token headers
+pub const KEY_ID: &str = "mock-key";
+
+/// Logs in as this subject unless `/authorize` is given a `login_hint`
+pub const DEFAULT_SUBJECT: &str = "mock-user";
+
+/// How long the issued ID tokens are valid
+const TOKEN_SECONDS: u64 = 300;
+
+/// Query parameter of `/authorize` that spoils the ID token issued for the code, to try out
+/// its validation: `issuer`, `audience`, `nonce`, `expired` or `signature`
+pub const TAMPER_PARAM: &str = "mock_tamper";
+
+/// Parameters of `/authorize` the provider acts on, the rest is ignored
+#[derive(Deserialize, Debug)]
+struct AuthorizeParams {
+    response_type: String,
+    client_id: String,
+    redirect_uri: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    login_hint: Option<String>,
+    mock_tamper: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenParams {
+    grant_type: String,
+    code: String,
+    redirect_uri: String,
+    code_verifier: Option<String>,
+}
+
+/// Login waiting for its code to be traded at `/token`
+#[derive(Debug, Clone)]
+struct Authorization {
+    redirect_uri: String,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    subject: String,
+    tamper: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MockIdentityProvider {
+    pub addr: SocketAddr,
+    pub client_id: String,
+    pub client_secret: String,
+    key: Arc<RsaKeyPair>,
+    codes: Arc<Mutex<HashMap<String, Authorization>>>,
+    issued: Arc<AtomicU64>,
+}
+
+impl MockIdentityProvider {
+    pub fn new(addr: SocketAddr, client_id: String, client_secret: String) -> Self {
+        let der = base64::decode(
+            SIGNING_KEY
+                .lines()
+                .filter(|line| !line.starts_with("-----"))
+                .collect::<String>(),
+        )
+        .expect("signing key is base64");
+
+        MockIdentityProvider {
+            addr,
+            client_id,
+            client_secret,
+            key: Arc::new(RsaKeyPair::from_pkcs8(&der).expect("signing key is an RSA key")),
+            codes: Arc::new(Mutex::new(HashMap::new())),
+            issued: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Serve the provider until the process is stopped
+    pub async fn run(self) {
+        let addr = self.addr;
+        warp::serve(self.routes()).run(addr).await
+    }
+
+    /// Serve the provider on a background task and return the address it listens on,
+    /// a free port is picked when the port of `addr` is 0
+    pub fn spawn(&self) -> SocketAddr {
+        let (addr, server) = warp::serve(self.clone().routes()).bind_ephemeral(self.addr);
+        tokio::spawn(server);
+        addr
+    }
+
+    /// The issuer is whatever host the provider was reached at
+    fn routes(self) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+        let issuer = warp::header::<String>("host").map(|host: String| format!("http://{}", host));
+
+        let discovery = warp::path!(".well-known" / "openid-configuration")
+            .and(warp::get())
+            .and(issuer)
+            .map(|issuer: String| {
+                warp::reply::json(&json!({
+                    "issuer": issuer,
+                    "authorization_endpoint": format!("{}/authorize", issuer),
+                    "token_endpoint": format!("{}/token", issuer),
+                    "jwks_uri": format!("{}/jwks", issuer),
+                    "response_types_supported": ["code"],
+                    "subject_types_supported": ["public"],
+                    "id_token_signing_alg_values_supported": ["RS256"],
+                    "code_challenge_methods_supported": ["S256"],
+                }))
+            });
+
+        let provider = self.clone();
+        let jwks = warp::path!("jwks")
+            .and(warp::get())
+            .map(move || warp::reply::json(&provider.jwks()));
+
+        let provider = self.clone();
+        let authorize = warp::path!("authorize")
+            .and(warp::get())
+            .and(warp::query::<AuthorizeParams>())
+            .map(move |params: AuthorizeParams| provider.authorize(params));
+
+        let provider = self;
+        let token = warp::path!("token")
+            .and(warp::post())
+            .and(issuer)
+            .and(warp::header::optional::<String>("authorization"))
+            .and(warp::body::form::<TokenParams>())
+            .map(
+                move |issuer: String, authorization: Option<String>, params: TokenParams| {
+                    provider.token(&issuer, authorization, params)
+                },
+            );
+
+        discovery.or(jwks).or(authorize).or(token)
+    }
+
+    fn jwks(&self) -> serde_json::Value {
+        let public_key = self.key.public_key();
+        json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": KEY_ID,
+                "use": "sig",
+                "alg": "RS256",
+                "n": encode(public_key.modulus().big_endian_without_leading_zero()),
+                "e": encode(public_key.exponent().big_endian_without_leading_zero()),
+            }]
+        })
+    }
+
+    /// Log in right away and redirect back with a code
+    fn authorize(&self, params: AuthorizeParams) -> warp::reply::Response {
+        if params.response_type != "code" || params.client_id != self.client_id {
+            return error(StatusCode::BAD_REQUEST, "unauthorized_client");
+        }
+
+        let code = format!("code-{}", self.issued.fetch_add(1, Ordering::SeqCst));
+        let mut query = vec![("code", code.clone())];
+        if let Some(state) = params.state {
+            query.push(("state", state));
+        }
+        let separator = if params.redirect_uri.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        let location = format!(
+            "{}{}{}",
+            params.redirect_uri,
+            separator,
+            serde_urlencoded::to_string(query).expect("query is a list of pairs")
+        );
+        let location = match location.parse::<Uri>() {
+            Ok(location) => location,
+            Err(_) => return error(StatusCode::BAD_REQUEST, "invalid_request"),
+        };
+
+        self.codes.lock().expect("codes poisoned").insert(
+            code,
+            Authorization {
+                redirect_uri: params.redirect_uri,
+                nonce: params.nonce,
+                code_challenge: params.code_challenge,
+                subject: params
+                    .login_hint
+                    .unwrap_or_else(|| DEFAULT_SUBJECT.to_owned()),
+                tamper: params.mock_tamper,
+            },
+        );
+
+        warp::reply::Reply::into_response(warp::redirect::found(location))
+    }
+
+    /// Trade a code for an ID token, each code works once
+    fn token(
+        &self,
+        issuer: &str,
+        authorization: Option<String>,
+        params: TokenParams,
+    ) -> warp::reply::Response {
+        let credentials = base64::encode(format!("{}:{}", self.client_id, self.client_secret));
+        if authorization != Some(format!("Basic {}", credentials)) {
+            return error(StatusCode::UNAUTHORIZED, "invalid_client");
+        }
+        if params.grant_type != "authorization_code" {
+            return error(StatusCode::BAD_REQUEST, "unsupported_grant_type");
+        }
+
+        let login = match self
+            .codes
+            .lock()
+            .expect("codes poisoned")
+            .remove(&params.code)
+        {
+            Some(login) => login,
+            None => return error(StatusCode::BAD_REQUEST, "invalid_grant"),
+        };
+        let challenge = params
+            .code_verifier
+            .map(|verifier| encode(digest(&SHA256, verifier.as_bytes()).as_ref()));
+        if login.redirect_uri != params.redirect_uri || login.code_challenge != challenge {
+            return error(StatusCode::BAD_REQUEST, "invalid_grant");
+        }
+
+        let id_token = self.id_token(issuer, &login);
+        warp::reply::Reply::into_response(warp::reply::json(&json!({
+            "access_token": format!("access-{}", params.code),
+            "token_type": "Bearer",
+            "expires_in": TOKEN_SECONDS,
+            "id_token": id_token,
+        })))
+    }
+
+    fn id_token(&self, issuer: &str, login: &Authorization) -> String {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("clock is after 1970")
+            .as_secs();
+        let tamper = login.tamper.as_deref();
+
+        let email = if login.subject.contains('@') {
+            login.subject.clone()
+        } else {
+            format!("{}@example.com", login.subject)
+        };
+        let claims = json!({
+            "iss": if tamper == Some("issuer") { "http://impostor.example.com" } else { issuer },
+            "sub": login.subject,
+            "aud": if tamper == Some("audience") { "another-client" } else { self.client_id.as_str() },
+            "iat": now,
+            "exp": if tamper == Some("expired") { now - 3600 } else { now + TOKEN_SECONDS },
+            "nonce": if tamper == Some("nonce") { Some("another-nonce") } else { login.nonce.as_deref() },
+            "email": email,
+            "email_verified": true,
+        });
+        let header = json!({ "alg": "RS256", "typ": "JWT", "kid": KEY_ID });
+
+        let message = format!(
+            "{}.{}",
+            encode(header.to_string().as_bytes()),
+            encode(claims.to_string().as_bytes())
+        );
+        let mut signature = vec![0; self.key.public_modulus_len()];
+        self.key
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .expect("signing never fails with a valid key");
+        if tamper == Some("signature") {
+            signature[0] ^= 1;
+        }
+
+        format!("{}.{}", message, encode(&signature))
+    }
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// OAuth error reply
+fn error(status: StatusCode, code: &str) -> warp::reply::Response {
+    warp::reply::Reply::into_response(warp::reply::with_status(
+        warp::reply::json(&json!({ "error": code })),
+        status,
+    ))
+}
@@ -0,0 +1,438 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use handle_errors::Error;
+
+/// Used by the wordlist filter unless `CONTENT_FILTER_WORDLIST` points to another list
+const DEFAULT_WORDLIST: &[&str] = &[
+    "arse",
+    "arsehole",
+    "asshole",
+    "bastard",
+    "bitch",
+    "bollocks",
+    "bullshit",
+    "crap",
+    "cunt",
+    "damn",
+    "dickhead",
+    "fuck",
+    "fucking",
+    "motherfucker",
+    "piss",
+    "shit",
+    "twat",
+    "wanker",
+];
+
+/// Text after running it through a content filter
+#[derive(Debug, Clone)]
+pub struct Censored {
+    /// The text with every bad word masked
+    pub text: String,
+    /// Bad words as they were found in the text
+    pub bad_words: Vec<String>,
+}
+
+/// Finds bad words in user content, implemented once per filter backend
+#[async_trait]
+pub trait ContentFilter: std::fmt::Debug + Send + Sync {
+    async fn censor(&self, text: &str) -> Result<Censored, Error>;
+}
+
+/// Masks the words of a fixed list, compared case-insensitively as whole words
+#[derive(Debug, Default)]
+pub struct WordlistFilter {
+    words: HashSet<String>,
+}
+
+impl WordlistFilter {
+    pub fn new<I: IntoIterator<Item = S>, S: AsRef<str>>(words: I) -> Self {
+        WordlistFilter {
+            words: words
+                .into_iter()
+                .map(|word| word.as_ref().trim().to_lowercase())
+                .filter(|word| !word.is_empty())
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl ContentFilter for WordlistFilter {
+    async fn censor(&self, text: &str) -> Result<Censored, Error> {
+        let mut censored = String::with_capacity(text.len());
+        let mut bad_words = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find(char::is_alphanumeric) {
+            censored.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+
+            if self.words.contains(&word.to_lowercase()) {
+                bad_words.push(word.to_string());
+                censored.extend(word.chars().map(|_| '*'));
+            } else {
+                censored.push_str(word);
+            }
+            rest = &rest[end..];
+        }
+        censored.push_str(rest);
+
+        Ok(Censored {
+            text: censored,
+            bad_words,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct BadWord {
+    original: String,
+}
+
+/// Reply of the bad words API
+#[derive(Deserialize, Debug)]
+struct BadWordsResponse {
+    bad_words_list: Vec<BadWord>,
+    censored_content: String,
+}
+
+/// Asks an HTTP bad words service, speaking the API of `api.apilayer.com/bad_words`
+#[derive(Debug)]
+pub struct HttpFilter {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    /// Limit of every single attempt
+    pub timeout: Duration,
+    /// Attempts made after the first one failed
+    pub retries: u32,
+    pub client: reqwest::Client,
+}
+
+impl HttpFilter {
+    async fn request(&self, text: &str) -> Result<Censored, RequestError> {
+        let mut request = self
+            .client
+            .post(format!(
+                "{}/bad_words?censor_character=*",
+                self.base_url.trim_end_matches('/')
+            ))
+            .timeout(self.timeout)
+            .body(text.to_string());
+        if let Some(api_key) = &self.api_key {
+            request = request.header("apikey", api_key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| RequestError::Retry(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            return Err(RequestError::Retry(format!("status {}", status)));
+        }
+        if !status.is_success() {
+            return Err(RequestError::Fail(format!("status {}", status)));
+        }
+
+        let res = response
+            .json::<BadWordsResponse>()
+            .await
+            .map_err(|e| RequestError::Fail(e.to_string()))?;
+
+        Ok(Censored {
+            text: res.censored_content,
+            bad_words: res.bad_words_list.into_iter().map(|w| w.original).collect(),
+        })
+    }
+}
+
+/// Failed request to the bad words service, only timeouts, network and server errors
+/// are worth another attempt
+enum RequestError {
+    Retry(String),
+    Fail(String),
+}
+
+#[async_trait]
+impl ContentFilter for HttpFilter {
+    async fn censor(&self, text: &str) -> Result<Censored, Error> {
+        let mut attempt = 0;
+        loop {
+            let reason = match self.request(text).await {
+                Ok(censored) => return Ok(censored),
+                Err(RequestError::Fail(reason)) => {
+                    return Err(Error::ContentFilterUnavailable(reason))
+                }
+                Err(RequestError::Retry(reason)) => reason,
+            };
+
+            if attempt >= self.retries {
+                return Err(Error::ContentFilterUnavailable(reason));
+            }
+            attempt += 1;
+            tracing::event!(
+                tracing::Level::WARN,
+                attempt,
+                "Bad words service failed, retrying: {}",
+                reason
+            );
+            tokio::time::sleep(Duration::from_millis(100 << attempt.min(5))).await;
+        }
+    }
+}
+
+/// What happens to text containing bad words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Store the text with the bad words masked
+    Censor,
+    /// Turn the request down
+    Reject,
+}
+
+/// What happens to text when the filter fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Accept the text unchecked
+    Open,
+    /// Turn the request down
+    Closed,
+}
+
+/// Runs question and answer text through the content filter
+#[derive(Debug, Clone)]
+pub struct ContentModerator {
+    pub filter: Arc<dyn ContentFilter>,
+    pub action: FilterAction,
+    pub failure_policy: FailurePolicy,
+}
+
+impl ContentModerator {
+    /// The text to store for `field`, censored or rejected when it contains bad words
+    pub async fn moderate(&self, field: &str, text: String) -> Result<String, Error> {
+        match self.filter.censor(&text).await {
+            Ok(censored) if censored.bad_words.is_empty() => Ok(text),
+            Ok(censored) => {
+                tracing::event!(
+                    tracing::Level::INFO,
+                    field,
+                    bad_words = censored.bad_words.len(),
+                    "Bad words found"
+                );
+                match self.action {
+                    FilterAction::Censor => Ok(censored.text),
+                    FilterAction::Reject => Err(Error::ContentRejected(field.to_string())),
+                }
+            }
+            Err(e) => match self.failure_policy {
+                FailurePolicy::Open => {
+                    tracing::event!(
+                        tracing::Level::WARN,
+                        field,
+                        "Content filter failed, accepting the text unchecked: {:?}",
+                        e
+                    );
+                    Ok(text)
+                }
+                FailurePolicy::Closed => Err(e),
+            },
+        }
+    }
+}
+
+/// Configure the filter from the environment.
+///
+/// `CONTENT_FILTER` picks the backend: `off` (the default) accepts everything, `wordlist` reads
+/// one word per line from `CONTENT_FILTER_WORDLIST` or uses a built-in list, and `http` calls the
+/// bad words service at `BAD_WORDS_API_URL` with `BAD_WORDS_API_KEY`, `BAD_WORDS_TIMEOUT_MS` and
+/// `BAD_WORDS_RETRIES`. `CONTENT_FILTER_ACTION` is `censor` or `reject`,
+/// `CONTENT_FILTER_FAILURE` is `open` or `closed`. Any other value stops the server from
+/// starting rather than silently filtering less than configured.
+pub fn from_env() -> ContentModerator {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    let filter: Arc<dyn ContentFilter> = match var("CONTENT_FILTER").as_deref() {
+        Some("http") => Arc::new(HttpFilter {
+            base_url: var("BAD_WORDS_API_URL")
+                .unwrap_or_else(|| "https://api.apilayer.com".to_owned()),
+            api_key: var("BAD_WORDS_API_KEY"),
+            timeout: Duration::from_millis(
+                var("BAD_WORDS_TIMEOUT_MS")
+                    .and_then(|ms| ms.parse().ok())
+                    .unwrap_or(2000),
+            ),
+            retries: var("BAD_WORDS_RETRIES")
+                .and_then(|retries| retries.parse().ok())
+                .unwrap_or(2),
+            client: reqwest::Client::new(),
+        }),
+        None | Some("off") => Arc::new(WordlistFilter::default()),
+        Some("wordlist") => match var("CONTENT_FILTER_WORDLIST") {
+            Some(path) => {
+                let words = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|e| panic!("Can't read wordlist {}: {}", path, e));
+                Arc::new(WordlistFilter::new(words.lines()))
+            }
+            None => Arc::new(WordlistFilter::new(DEFAULT_WORDLIST)),
+        },
+        Some(other) => panic!("Unknown CONTENT_FILTER {}", other),
+    };
+
+    ContentModerator {
+        filter,
+        action: match var("CONTENT_FILTER_ACTION").as_deref() {
+            None | Some("censor") => FilterAction::Censor,
+            Some("reject") => FilterAction::Reject,
+            Some(other) => panic!("Unknown CONTENT_FILTER_ACTION {}", other),
+        },
+        failure_policy: match var("CONTENT_FILTER_FAILURE").as_deref() {
+            None | Some("open") => FailurePolicy::Open,
+            Some("closed") => FailurePolicy::Closed,
+            Some(other) => panic!("Unknown CONTENT_FILTER_FAILURE {}", other),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::{MockServer, FAIL_MARKER};
+    use std::net::SocketAddr;
+
+    fn moderator(filter: impl ContentFilter + 'static, action: FilterAction) -> ContentModerator {
+        ContentModerator {
+            filter: Arc::new(filter),
+            action,
+            failure_policy: FailurePolicy::Closed,
+        }
+    }
+
+    fn http_filter(addr: SocketAddr, retries: u32) -> HttpFilter {
+        HttpFilter {
+            base_url: format!("http://{}/", addr),
+            api_key: None,
+            timeout: Duration::from_secs(2),
+            retries,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn mock_server() -> MockServer {
+        MockServer::new(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            vec!["shit".to_string(), "damn".to_string()],
+        )
+    }
+
+    #[tokio::test]
+    async fn wordlist_masks_whole_words_case_insensitively() {
+        let filter = WordlistFilter::new(["Damn", " crap ", ""]);
+
+        let censored = filter
+            .censor("DAMN, that's crappy crap. Über-damn!")
+            .await
+            .unwrap();
+
+        assert_eq!(censored.text, "****, that's crappy ****. Über-****!");
+        assert_eq!(censored.bad_words, vec!["DAMN", "crap", "damn"]);
+    }
+
+    #[tokio::test]
+    async fn wordlist_leaves_clean_text_alone() {
+        let censored = WordlistFilter::new(DEFAULT_WORDLIST)
+            .censor("  Scunthorpe is fine.\n")
+            .await
+            .unwrap();
+
+        assert_eq!(censored.text, "  Scunthorpe is fine.\n");
+        assert!(censored.bad_words.is_empty());
+    }
+
+    #[tokio::test]
+    async fn http_filter_uses_the_service() {
+        let server = mock_server();
+        let addr = server.spawn();
+
+        let censored = http_filter(addr, 2).censor("Damn it").await.unwrap();
+
+        assert_eq!(censored.text, "**** it");
+        assert_eq!(censored.bad_words, vec!["Damn"]);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn http_filter_retries_server_errors() {
+        let server = mock_server();
+        let addr = server.spawn();
+
+        let result = http_filter(addr, 2).censor(FAIL_MARKER).await;
+
+        assert!(matches!(result, Err(Error::ContentFilterUnavailable(_))));
+        assert_eq!(server.request_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn http_filter_does_not_retry_client_errors() {
+        let server = mock_server();
+        let addr = server.spawn();
+        let mut filter = http_filter(addr, 2);
+        filter.base_url = format!("http://{}/missing", addr);
+
+        let result = filter.censor("Damn it").await;
+
+        assert!(matches!(result, Err(Error::ContentFilterUnavailable(_))));
+        assert_eq!(server.request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn failing_filter_follows_the_failure_policy() {
+        let server = mock_server();
+        let addr = server.spawn();
+        let text = format!("damn {}", FAIL_MARKER);
+
+        let mut moderator = moderator(http_filter(addr, 0), FilterAction::Censor);
+        moderator.failure_policy = FailurePolicy::Open;
+        assert_eq!(
+            moderator.moderate("content", text.clone()).await.unwrap(),
+            text
+        );
+
+        moderator.failure_policy = FailurePolicy::Closed;
+        assert!(matches!(
+            moderator.moderate("content", text).await,
+            Err(Error::ContentFilterUnavailable(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn moderator_censors_or_rejects_bad_words() {
+        let censor = moderator(WordlistFilter::new(["damn"]), FilterAction::Censor);
+        assert_eq!(
+            censor
+                .moderate("title", "Damn it".to_string())
+                .await
+                .unwrap(),
+            "**** it"
+        );
+
+        let reject = moderator(WordlistFilter::new(["damn"]), FilterAction::Reject);
+        assert!(matches!(
+            reject.moderate("title", "Damn it".to_string()).await,
+            Err(Error::ContentRejected(field)) if field == "title"
+        ));
+        assert_eq!(
+            reject.moderate("title", "Fine".to_string()).await.unwrap(),
+            "Fine"
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use handle_errors::Error;
+
+/// An email about to be sent to an account
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers emails, implemented once per delivery backend
+#[async_trait]
+pub trait Mailer: std::fmt::Debug + Send + Sync {
+    async fn send(&self, email: Email) -> Result<(), Error>;
+}
+
+/// Writes emails to the log, meant for local development
+#[derive(Debug, Default)]
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        tracing::event!(
+            tracing::Level::INFO,
+            to = %email.to,
+            subject = %email.subject,
+            "{}",
+            email.body
+        );
+        Ok(())
+    }
+}
+
+/// Writes every email to its own file in a directory, meant for local development and tests
+#[derive(Debug)]
+pub struct FileMailer {
+    pub dir: PathBuf,
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, email: Email) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| Error::MailerError(e.to_string()))?;
+
+        let path = self.dir.join(format!(
+            "{}-{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            uuid::Uuid::new_v4()
+        ));
+        let content = format!(
+            "To: {}\nSubject: {}\n\n{}\n",
+            email.to, email.subject, email.body
+        );
+
+        tokio::fs::write(&path, content)
+            .await
+            .map_err(|e| Error::MailerError(e.to_string()))
+    }
+}
+
+/// Pick the mailer from the `MAILER` environment variable, `file` writes to `MAILER_DIR`
+pub fn from_env() -> Arc<dyn Mailer> {
+    match std::env::var("MAILER").as_deref() {
+        Ok("file") => Arc::new(FileMailer {
+            dir: std::env::var("MAILER_DIR")
+                .unwrap_or_else(|_| "mails".to_owned())
+                .into(),
+        }),
+        _ => Arc::new(LogMailer),
+    }
+}
@@ -1,9 +1,18 @@
-use dotenv;
 use handle_errors::return_error;
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing_subscriber::fmt::format::FmtSpan;
+use types::account::{DeletionPolicy, Role};
+use types::api_key::ApiKeyScope;
+use types::moderation::{ContentKind, FlagThreshold, ModerationAction, ModerationNote};
 use warp::{http::Method, Filter}; // Bring the Filter trait to scope for using `map`
 
+mod content_filter;
+mod mailer;
+mod oidc;
+mod purge;
+mod rate_limit;
 mod routes;
 mod store;
 mod types;
@@ -12,7 +21,7 @@ mod types;
 async fn main() -> Result<(), handle_errors::Error> {
     dotenv::dotenv().ok();
 
-    if let Err(_) = env::var("PASETO_KEY") {
+    if env::var("PASETO_KEY").is_err() {
         panic!("PASETO key not set!")
     }
 
@@ -20,7 +29,7 @@ async fn main() -> Result<(), handle_errors::Error> {
         .ok()
         .map(|val| val.parse::<u16>())
         .unwrap_or(Ok(8080))
-        .map_err(|e| handle_errors::Error::ParseError(e))?;
+        .map_err(handle_errors::Error::ParseError)?;
 
     let log_filter =
         std::env::var("RUST_LOG").unwrap_or_else(|_| "warp_exp=info,warp=error".to_owned());
@@ -35,7 +44,7 @@ async fn main() -> Result<(), handle_errors::Error> {
         .ok()
         .map(|val| val.parse::<u16>())
         .unwrap_or(Ok(5432))
-        .map_err(|e| handle_errors::Error::ParseError(e))?;
+        .map_err(handle_errors::Error::ParseError)?;
 
     let postgres_host = std::env::var("POSTGRES_HOST").unwrap_or_else(|_| "localhost".to_owned());
 
@@ -47,95 +56,714 @@ async fn main() -> Result<(), handle_errors::Error> {
         .as_str(),
     )
     .await
-    .map_err(|e| handle_errors::Error::DatabaseQueryError(e))?;
+    .map_err(handle_errors::Error::DatabaseQueryError)?;
 
     // Do SQL Migration whenever the server startup
     sqlx::migrate!()
         .run(&store.clone().connection)
         .await
-        .map_err(|e| handle_errors::Error::MigrationError(e))?;
+        .map_err(handle_errors::Error::MigrationError)?;
 
-    let store_filter = warp::any().map(move || store.clone());
+    let store_filter = {
+        let store = store.clone();
+        warp::any().map(move || store.clone())
+    };
+
+    let mailer = mailer::from_env();
+    let mailer_filter = warp::any().map(move || mailer.clone());
+
+    let moderator = Arc::new(content_filter::from_env());
+    let moderator_filter = warp::any().map(move || moderator.clone());
+
+    let oidc = oidc::from_env();
+    let oidc_filter = warp::any().map(move || oidc.clone());
+
+    // Listings are cheap to serve, posting content is limited much more strictly
+    let read_limiter = rate_limit::RateLimiter::new(120, Duration::from_secs(60));
+    let write_limiter = rate_limit::RateLimiter::new(10, Duration::from_secs(60));
+
+    let deletion_policy = DeletionPolicy::from_env();
+    let deletion_policy_filter = warp::any().map(move || deletion_policy);
+
+    let flag_threshold = FlagThreshold::from_env();
+    let flag_threshold_filter = warp::any().map(move || flag_threshold);
+
+    let retention_policy = purge::RetentionPolicy::from_env();
+    let retention_policy_filter = warp::any().map(move || retention_policy);
 
     tracing_subscriber::fmt()
         .with_env_filter(log_filter)
         .with_span_events(FmtSpan::CLOSE)
         .init();
 
+    // Deleted content is purged for good once its retention period is over
+    tokio::spawn(purge::run(store.clone(), retention_policy));
+
     let cors = warp::cors()
         .allow_any_origin()
-        .allow_header("content-type")
-        .allow_methods(&[Method::PUT, Method::DELETE, Method::GET, Method::POST]);
+        .allow_headers(vec!["content-type", "if-match", "authorization"])
+        .expose_headers(vec![
+            "x-ratelimit-limit",
+            "x-ratelimit-remaining",
+            "x-ratelimit-reset",
+            "etag",
+            "link",
+        ])
+        .allow_methods(&[
+            Method::PUT,
+            Method::DELETE,
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+        ]);
 
-    let get_questions = warp::get()
-        .and(warp::path("questions"))
+    // Every route matches its path before its method, so unknown paths are answered with a
+    // 404 and known paths called with the wrong method with a 405.
+    let get_questions = warp::path("questions")
         .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
         .and(warp::query())
+        .and(routes::authentication::optional_auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::question::get_questions)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let get_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(routes::authentication::optional_auth(store.clone()))
         .and(store_filter.clone())
-        .and_then(routes::question::get_questions);
+        .and_then(routes::question::get_question)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
 
-    let add_question = warp::post()
+    let get_tags = warp::path("tags")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::question::get_tags);
+
+    let get_tag_questions = warp::path("tags")
+        .and(warp::path::param::<String>())
         .and(warp::path("questions"))
         .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(warp::query())
+        .and(routes::authentication::optional_auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::question::get_tag_questions)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let add_question = warp::path("questions")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(write_limiter.limit())
         .and(store_filter.clone())
-        .and(routes::authentication::auth())
+        .and(moderator_filter.clone())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
         .and(warp::body::json())
-        .and_then(routes::question::add_question);
+        .and_then(routes::question::add_question)
+        .and(write_limiter.status())
+        .map(rate_limit::with_headers);
 
-    let update_question = warp::put()
-        .and(warp::path("questions"))
+    let update_question = warp::path("questions")
         .and(warp::path::param::<i32>()) // Add a string parameter ex: /questions/1234.
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
         .and(store_filter.clone())
+        .and(moderator_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
         .and(warp::body::json())
         .and_then(routes::question::update_question);
 
-    let delete_question = warp::delete()
-        .and(warp::path("questions"))
+    let delete_question = warp::path("questions")
         .and(warp::path::param::<i32>())
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
         .and(store_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
         .and_then(routes::question::delete_question);
 
-    let add_answer = warp::post()
+    let undelete_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("undelete"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and(retention_policy_filter)
+        .and_then(routes::question::undelete_question);
+
+    let get_question_revisions = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::question::get_question_revisions)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let rollback_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("rollback"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
+        .and_then(routes::question::rollback_question);
+
+    let accept_answer = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accepted-answer"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::question::accept_answer);
+
+    let unaccept_answer = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("accepted-answer"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::unaccept_answer);
+
+    let vote_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::question::vote_question);
+
+    let retract_question_vote = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::retract_question_vote);
+
+    let bookmark_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("bookmark"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::bookmark_question);
+
+    let remove_bookmark = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("bookmark"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::question::remove_bookmark);
+
+    let flag_question = warp::path("questions")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("flags"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteQuestions,
+        ))
+        .and(store_filter.clone())
+        .and(flag_threshold_filter)
+        .and(warp::body::json())
+        .and_then(routes::moderation::flag_question);
+
+    let get_answers = warp::path("questions")
+        .and(warp::path::param::<i32>())
         .and(warp::path("answers"))
         .and(warp::path::end())
-        .and(routes::authentication::auth())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(warp::query())
+        .and(routes::authentication::optional_auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::answer::get_answers)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let add_answer = warp::path("answers")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(write_limiter.limit())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
         .and(store_filter.clone())
+        .and(moderator_filter.clone())
         .and(warp::body::form())
-        .and_then(routes::answer::add_answer);
+        .and_then(routes::answer::add_answer)
+        .and(write_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let update_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and(moderator_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(warp::body::json())
+        .and_then(routes::answer::update_answer);
 
-    let registration = warp::post()
-        .and(warp::path("registration"))
+    let delete_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
+        .and_then(routes::answer::delete_answer);
+
+    let undelete_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("undelete"))
         .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and(retention_policy_filter)
+        .and_then(routes::answer::undelete_answer);
+
+    let get_answer_revisions = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::answer::get_answer_revisions)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let rollback_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("revisions"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("rollback"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and(warp::header::optional::<String>("if-match"))
+        .and_then(routes::answer::rollback_answer);
+
+    let vote_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
         .and(store_filter.clone())
         .and(warp::body::json())
+        .and_then(routes::answer::vote_answer);
+
+    let retract_answer_vote = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("vote"))
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::answer::retract_answer_vote);
+
+    let flag_answer = warp::path("answers")
+        .and(warp::path::param::<i32>())
+        .and(warp::path("flags"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::WriteAnswers,
+        ))
+        .and(store_filter.clone())
+        .and(flag_threshold_filter)
+        .and(warp::body::json())
+        .and_then(routes::moderation::flag_answer);
+
+    let search = warp::path("search")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(read_limiter.limit())
+        .and(warp::query())
+        .and(store_filter.clone())
+        .and_then(routes::search::search)
+        .and(read_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let registration = warp::path("registration")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
         .and_then(routes::authentication::register);
 
-    let login = warp::post()
-        .and(warp::path("login"))
+    let verify_email = warp::path("verify-email")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::verify_email);
+
+    let resend_verification = warp::path("verify-email")
+        .and(warp::path("resend"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(write_limiter.limit())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::resend_verification)
+        .and(write_limiter.status())
+        .map(rate_limit::with_headers);
+
+    let login = warp::path("login")
         .and(warp::path::end())
+        .and(warp::post())
         .and(store_filter.clone())
+        .and(routes::authentication::client_ip())
         .and(warp::body::json())
         .and_then(routes::authentication::login);
 
-    let reset_password = warp::post()
-        .and(warp::path("reset-password"))
+    let oidc_login = warp::path("oidc")
+        .and(warp::path("login"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(oidc_filter.clone())
+        .and(store_filter.clone())
+        .and_then(routes::oidc::oidc_login);
+
+    let oidc_callback = warp::path("oidc")
+        .and(warp::path("callback"))
         .and(warp::path::end())
+        .and(warp::get())
+        .and(oidc_filter.clone())
+        .and(store_filter.clone())
+        .and(warp::query())
+        .and_then(routes::oidc::oidc_callback);
+
+    let refresh_token = warp::path("token")
+        .and(warp::path("refresh"))
+        .and(warp::path::end())
+        .and(warp::post())
         .and(store_filter.clone())
         .and(warp::body::json())
-        .and_then(routes::authentication::reset_password);
+        .and_then(routes::authentication::refresh_token);
 
-    let routes = get_questions
+    let logout = warp::path("logout")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::authentication::logout);
+
+    let logout_everywhere = warp::path("logout")
+        .and(warp::path("all"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::authentication::logout_everywhere);
+
+    let request_password_reset = warp::path("reset-password")
+        .and(warp::path("request"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(mailer_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::request_password_reset);
+
+    let confirm_password_reset = warp::path("reset-password")
+        .and(warp::path("confirm"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::confirm_password_reset);
+
+    let change_password = warp::path("change-password")
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::authentication::change_password);
+
+    let get_me = warp::path("me")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::authentication::require_scope(
+            store.clone(),
+            ApiKeyScope::Read,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::account::get_me);
+
+    let update_me = warp::path("me")
+        .and(warp::path::end())
+        .and(warp::patch())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::account::update_me);
+
+    let delete_me = warp::path("me")
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and(deletion_policy_filter)
+        .and(warp::body::json())
+        .and_then(routes::account::delete_me);
+
+    let export_me = warp::path("me")
+        .and(warp::path("export"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::account::export_me);
+
+    let create_api_key = warp::path("me")
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::api_key::create_api_key);
+
+    let get_api_keys = warp::path("me")
+        .and(warp::path("api-keys"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::api_key::get_api_keys);
+
+    let revoke_api_key = warp::path("me")
+        .and(warp::path("api-keys"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
+        .and(warp::delete())
+        .and(routes::authentication::auth(store.clone()))
+        .and(store_filter.clone())
+        .and_then(routes::api_key::revoke_api_key);
+
+    let get_user = warp::path("users")
+        .and(warp::path::param::<i32>())
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(store_filter.clone())
+        .and_then(routes::account::get_user);
+
+    let get_accounts = warp::path("admin")
+        .and(warp::path("accounts"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(routes::authentication::require_role(
+            store.clone(),
+            Role::Admin,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::admin::get_accounts);
+
+    let set_account_role = warp::path("admin")
+        .and(warp::path("accounts"))
+        .and(warp::path::param::<i32>())
+        .and(warp::path("role"))
+        .and(warp::path::end())
+        .and(warp::put())
+        .and(routes::authentication::require_role(
+            store.clone(),
+            Role::Admin,
+        ))
+        .and(store_filter.clone())
+        .and(warp::body::json())
+        .and_then(routes::admin::set_account_role);
+
+    let get_moderation_queue = warp::path("moderation")
+        .and(warp::path("queue"))
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query())
+        .and(routes::authentication::require_role(
+            store.clone(),
+            Role::Moderator,
+        ))
+        .and(store_filter.clone())
+        .and_then(routes::moderation::get_queue);
+
+    // POST /moderation/{questions|answers}/{id}/{approve|remove|restore}
+    let moderate = warp::path("moderation")
+        .and(warp::path::param::<ContentKind>())
+        .and(warp::path::param::<i32>())
+        .and(warp::path::param::<ModerationAction>())
+        .and(warp::path::end())
+        .and(warp::post())
+        .and(routes::authentication::require_role(
+            store.clone(),
+            Role::Moderator,
+        ))
+        .and(store_filter.clone())
+        // The note is optional, so is the body
+        .and(
+            warp::body::json()
+                .or(warp::any().map(ModerationNote::default))
+                .unify(),
+        )
+        .and_then(routes::moderation::moderate);
+
+    // Every group is boxed, one deeply nested filter overflows the stack of debug builds
+    let question_routes = get_questions
+        .or(get_question)
+        .or(get_tags)
+        .or(get_tag_questions)
         .or(add_question)
         .or(update_question)
         .or(delete_question)
+        .or(undelete_question)
+        .or(get_question_revisions)
+        .or(rollback_question)
+        .or(accept_answer)
+        .or(unaccept_answer)
+        .or(vote_question)
+        .or(retract_question_vote)
+        .or(bookmark_question)
+        .or(remove_bookmark)
+        .or(flag_question)
+        .boxed();
+
+    let answer_routes = get_answers
         .or(add_answer)
-        .or(registration)
+        .or(update_answer)
+        .or(delete_answer)
+        .or(undelete_answer)
+        .or(get_answer_revisions)
+        .or(rollback_answer)
+        .or(vote_answer)
+        .or(retract_answer_vote)
+        .or(flag_answer)
+        .or(search)
+        .boxed();
+
+    let auth_routes = registration
+        .or(verify_email)
+        .or(resend_verification)
         .or(login)
-        .or(reset_password)
+        .or(oidc_login)
+        .or(oidc_callback)
+        .or(refresh_token)
+        .or(logout)
+        .or(logout_everywhere)
+        .or(request_password_reset)
+        .or(confirm_password_reset)
+        .or(change_password)
+        .boxed();
+
+    let account_routes = get_me
+        .or(update_me)
+        .or(delete_me)
+        .or(export_me)
+        .or(create_api_key)
+        .or(get_api_keys)
+        .or(revoke_api_key)
+        .or(get_user)
+        .or(get_accounts)
+        .or(set_account_role)
+        .boxed();
+
+    let moderation_routes = get_moderation_queue.or(moderate).boxed();
+
+    let routes = question_routes
+        .or(answer_routes)
+        .or(auth_routes)
+        .or(account_routes)
+        .or(moderation_routes)
         .with(cors)
         .with(warp::trace::request())
         .recover(return_error);
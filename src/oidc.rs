@@ -0,0 +1,487 @@
+use ring::digest::{digest, SHA256};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::{OnceCell, RwLock};
+
+use crate::types::oidc::{ExternalIdentity, PendingOidcLogin};
+use handle_errors::Error;
+
+/// Leeway for clocks of the identity provider running ahead or behind
+const CLOCK_SKEW_SECONDS: i64 = 60;
+
+/// Endpoints of the identity provider, read from its discovery document
+#[derive(Deserialize, Debug, Clone)]
+struct ProviderMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+/// Public key of the identity provider, only RSA keys are used
+#[derive(Deserialize, Debug, Clone)]
+struct Jwk {
+    kty: String,
+    #[serde(default)]
+    kid: Option<String>,
+    #[serde(default)]
+    n: Option<String>,
+    #[serde(default)]
+    e: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct IdTokenHeader {
+    alg: String,
+    #[serde(default)]
+    kid: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize, Debug)]
+struct IdTokenClaims {
+    iss: String,
+    sub: String,
+    aud: Audience,
+    exp: i64,
+    #[serde(default)]
+    nonce: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    /// Some providers send the flag as a string
+    #[serde(default)]
+    email_verified: Option<serde_json::Value>,
+}
+
+/// OpenID Connect client doing the authorization code flow with one identity provider
+#[derive(Debug)]
+pub struct OidcClient {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    scopes: String,
+    /// Replaces the `jwks_uri` of the discovery document when set
+    jwks_uri: Option<String>,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    keys: RwLock<Vec<Jwk>>,
+}
+
+/// Configure the identity provider from `OIDC_ISSUER`, `OIDC_CLIENT_ID`, `OIDC_CLIENT_SECRET`
+/// and `OIDC_REDIRECT_URI`, login with it is disabled unless they are all set.
+///
+/// The endpoints are discovered from `<issuer>/.well-known/openid-configuration`,
+/// `OIDC_JWKS_URI` overrides where the signing keys are fetched from and `OIDC_SCOPES`
+/// the scopes asked for.
+pub fn from_env() -> Option<Arc<OidcClient>> {
+    let var = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+    Some(Arc::new(OidcClient {
+        issuer: var("OIDC_ISSUER")?.trim_end_matches('/').to_string(),
+        client_id: var("OIDC_CLIENT_ID")?,
+        client_secret: var("OIDC_CLIENT_SECRET")?,
+        redirect_uri: var("OIDC_REDIRECT_URI")?,
+        scopes: var("OIDC_SCOPES").unwrap_or_else(|| "openid email".to_owned()),
+        jwks_uri: var("OIDC_JWKS_URI"),
+        http: reqwest::Client::new(),
+        metadata: OnceCell::new(),
+        keys: RwLock::new(Vec::new()),
+    }))
+}
+
+impl OidcClient {
+    /// Where to send the browser to log in with the identity provider
+    pub async fn authorization_url(&self, login: &PendingOidcLogin) -> Result<String, Error> {
+        let metadata = self.metadata().await?;
+        let code_challenge = base64::encode_config(
+            digest(&SHA256, login.code_verifier.as_bytes()),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", self.client_id.as_str()),
+            ("redirect_uri", self.redirect_uri.as_str()),
+            ("scope", self.scopes.as_str()),
+            ("state", login.state.as_str()),
+            ("nonce", login.nonce.as_str()),
+            ("code_challenge", code_challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ])
+        .map_err(|e| Error::OidcProviderError(e.to_string()))?;
+
+        let separator = if metadata.authorization_endpoint.contains('?') {
+            '&'
+        } else {
+            '?'
+        };
+        Ok(format!(
+            "{}{}{}",
+            metadata.authorization_endpoint, separator, query
+        ))
+    }
+
+    /// Trade the authorization code for an ID token and return the identity it was issued for
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        login: &PendingOidcLogin,
+    ) -> Result<ExternalIdentity, Error> {
+        let metadata = self.metadata().await?;
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("code_verifier", login.code_verifier.as_str()),
+            ])
+            .send()
+            .await
+            .map_err(|e| Error::OidcProviderError(e.to_string()))?;
+
+        // The provider turns down codes that are unknown, expired or already used
+        if response.status().is_client_error() {
+            tracing::event!(
+                tracing::Level::WARN,
+                status = response.status().as_u16(),
+                "Authorization code rejected by the identity provider"
+            );
+            return Err(Error::InvalidOidcLogin);
+        }
+
+        let tokens = response
+            .error_for_status()
+            .map_err(|e| Error::OidcProviderError(e.to_string()))?
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| Error::OidcProviderError(e.to_string()))?;
+
+        self.verify_id_token(&tokens.id_token, &login.nonce)
+            .await
+            .map_err(|reason| match reason {
+                IdTokenError::Provider(e) => e,
+                IdTokenError::Invalid(reason) => {
+                    tracing::event!(tracing::Level::WARN, "ID token rejected: {}", reason);
+                    Error::InvalidIdToken
+                }
+            })
+    }
+
+    async fn verify_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<ExternalIdentity, IdTokenError> {
+        let mut parts = id_token.split('.');
+        let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(payload), Some(signature)) if parts.next().is_none() => {
+                (header, payload, signature)
+            }
+            _ => return Err(IdTokenError::invalid("not a signed JWT")),
+        };
+
+        let header: IdTokenHeader = decode_json(header)?;
+        if header.alg != "RS256" {
+            return Err(IdTokenError::invalid(format!(
+                "unsupported algorithm {}",
+                header.alg
+            )));
+        }
+
+        let key = self.key(header.kid.as_deref()).await?;
+        let (n, e) = match (&key.n, &key.e) {
+            (Some(n), Some(e)) => (decode_base64(n)?, decode_base64(e)?),
+            _ => {
+                return Err(IdTokenError::invalid(
+                    "signing key has no modulus or exponent",
+                ))
+            }
+        };
+        let message = &id_token[..id_token.len() - signature.len() - 1];
+        RsaPublicKeyComponents { n, e }
+            .verify(
+                &RSA_PKCS1_2048_8192_SHA256,
+                message.as_bytes(),
+                &decode_base64(signature)?,
+            )
+            .map_err(|_| IdTokenError::invalid("bad signature"))?;
+
+        let claims: IdTokenClaims = decode_json(payload)?;
+        if claims.iss.trim_end_matches('/') != self.issuer {
+            return Err(IdTokenError::invalid(format!("issued by {}", claims.iss)));
+        }
+        let audience_matches = match &claims.aud {
+            Audience::One(aud) => *aud == self.client_id,
+            Audience::Many(auds) => auds.contains(&self.client_id),
+        };
+        if !audience_matches {
+            return Err(IdTokenError::invalid("issued for another client"));
+        }
+        if claims.exp + CLOCK_SKEW_SECONDS < chrono::Utc::now().timestamp() {
+            return Err(IdTokenError::invalid("expired"));
+        }
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(IdTokenError::invalid("nonce does not match the login"));
+        }
+
+        let email_verified = match claims.email_verified {
+            Some(serde_json::Value::Bool(verified)) => verified,
+            Some(serde_json::Value::String(verified)) => verified == "true",
+            _ => false,
+        };
+
+        Ok(ExternalIdentity {
+            issuer: self.issuer.clone(),
+            subject: claims.sub,
+            email: claims.email,
+            email_verified,
+        })
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, Error> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!("{}/.well-known/openid-configuration", self.issuer);
+                self.http
+                    .get(&url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .map_err(|e| Error::OidcProviderError(e.to_string()))?
+                    .json::<ProviderMetadata>()
+                    .await
+                    .map_err(|e| Error::OidcProviderError(e.to_string()))
+            })
+            .await
+    }
+
+    /// Signing key with the id `kid`, the keys are fetched again when it is unknown since
+    /// the provider may have rotated them
+    async fn key(&self, kid: Option<&str>) -> Result<Jwk, IdTokenError> {
+        let find = |keys: &[Jwk]| {
+            keys.iter()
+                .find(|key| key.kty == "RSA" && (kid.is_none() || key.kid.as_deref() == kid))
+                .cloned()
+        };
+
+        if let Some(key) = find(&self.keys.read().await) {
+            return Ok(key);
+        }
+
+        let jwks_uri = match &self.jwks_uri {
+            Some(jwks_uri) => jwks_uri.clone(),
+            None => self
+                .metadata()
+                .await
+                .map_err(IdTokenError::Provider)?
+                .jwks_uri
+                .clone(),
+        };
+        let fetched = self
+            .http
+            .get(&jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| IdTokenError::Provider(Error::OidcProviderError(e.to_string())))?
+            .json::<JwkSet>()
+            .await
+            .map_err(|e| IdTokenError::Provider(Error::OidcProviderError(e.to_string())))?;
+
+        let mut keys = self.keys.write().await;
+        *keys = fetched.keys;
+        find(&keys).ok_or_else(|| IdTokenError::invalid("signed with an unknown key"))
+    }
+}
+
+/// Why an ID token was not accepted, problems reaching the provider are kept apart from
+/// tokens that are invalid
+enum IdTokenError {
+    Provider(Error),
+    Invalid(String),
+}
+
+impl IdTokenError {
+    fn invalid(reason: impl Into<String>) -> Self {
+        IdTokenError::Invalid(reason.into())
+    }
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, IdTokenError> {
+    base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+        .map_err(|_| IdTokenError::invalid("bad base64url encoding"))
+}
+
+fn decode_json<T: serde::de::DeserializeOwned>(value: &str) -> Result<T, IdTokenError> {
+    serde_json::from_slice(&decode_base64(value)?)
+        .map_err(|e| IdTokenError::invalid(format!("bad JSON: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_server::oidc::{MockIdentityProvider, DEFAULT_SUBJECT, TAMPER_PARAM};
+    use std::net::SocketAddr;
+
+    const REDIRECT_URI: &str = "http://localhost:3030/oidc/callback";
+
+    fn provider() -> (OidcClient, SocketAddr) {
+        let addr = MockIdentityProvider::new(
+            SocketAddr::from(([127, 0, 0, 1], 0)),
+            "client".to_string(),
+            "secret".to_string(),
+        )
+        .spawn();
+
+        let client = OidcClient {
+            issuer: format!("http://{}", addr),
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: REDIRECT_URI.to_string(),
+            scopes: "openid email".to_string(),
+            jwks_uri: None,
+            http: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+            keys: RwLock::new(Vec::new()),
+        };
+        (client, addr)
+    }
+
+    fn pending_login() -> PendingOidcLogin {
+        PendingOidcLogin {
+            state: "state".to_string(),
+            nonce: "nonce".to_string(),
+            code_verifier: "verifier".to_string(),
+            expires_on: chrono::Utc::now() + chrono::Duration::minutes(10),
+        }
+    }
+
+    /// Log in at the provider, spoiling the ID token with `tamper`, and return the code it
+    /// redirected back with
+    async fn authorize(client: &OidcClient, login: &PendingOidcLogin, tamper: &str) -> String {
+        let url = format!(
+            "{}&{}={}",
+            client.authorization_url(login).await.unwrap(),
+            TAMPER_PARAM,
+            tamper
+        );
+        let response = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await
+            .unwrap();
+
+        let location = response.headers()["location"].to_str().unwrap();
+        let query = location
+            .strip_prefix(&format!("{}?", REDIRECT_URI))
+            .unwrap();
+        let params: std::collections::HashMap<String, String> =
+            serde_urlencoded::from_str(query).unwrap();
+        assert_eq!(params["state"], login.state);
+        params["code"].clone()
+    }
+
+    #[tokio::test]
+    async fn exchanges_the_code_for_the_identity() {
+        let (client, addr) = provider();
+        let login = pending_login();
+
+        let code = authorize(&client, &login, "none").await;
+        let identity = client.exchange_code(&code, &login).await.unwrap();
+
+        assert_eq!(identity.issuer, format!("http://{}", addr));
+        assert_eq!(identity.subject, DEFAULT_SUBJECT);
+        assert_eq!(identity.email.as_deref(), Some("mock-user@example.com"));
+        assert!(identity.email_verified);
+    }
+
+    #[tokio::test]
+    async fn codes_work_once() {
+        let (client, _) = provider();
+        let login = pending_login();
+
+        let code = authorize(&client, &login, "none").await;
+        client.exchange_code(&code, &login).await.unwrap();
+
+        assert!(matches!(
+            client.exchange_code(&code, &login).await,
+            Err(Error::InvalidOidcLogin)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_wrong_code_verifier() {
+        let (client, _) = provider();
+        let login = pending_login();
+
+        let code = authorize(&client, &login, "none").await;
+        let other = PendingOidcLogin {
+            code_verifier: "another-verifier".to_string(),
+            ..pending_login()
+        };
+
+        assert!(matches!(
+            client.exchange_code(&code, &other).await,
+            Err(Error::InvalidOidcLogin)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_spoiled_id_tokens() {
+        let (client, _) = provider();
+        let login = pending_login();
+
+        for tamper in ["issuer", "audience", "nonce", "expired", "signature"] {
+            let code = authorize(&client, &login, tamper).await;
+            assert!(
+                matches!(
+                    client.exchange_code(&code, &login).await,
+                    Err(Error::InvalidIdToken)
+                ),
+                "token with a bad {} accepted",
+                tamper
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_a_nonce_of_another_login() {
+        let (client, _) = provider();
+        let login = pending_login();
+
+        let code = authorize(&client, &login, "none").await;
+        let other = PendingOidcLogin {
+            nonce: "another-nonce".to_string(),
+            ..pending_login()
+        };
+
+        assert!(matches!(
+            client.exchange_code(&code, &other).await,
+            Err(Error::InvalidIdToken)
+        ));
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::{Duration, Utc};
+
+use crate::store::Store;
+use crate::types::account::{Role, Session};
+use crate::types::deletion::Deletion;
+use handle_errors::Error;
+
+/// How long soft deleted questions and answers are kept around
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    /// Time in which the author may undelete their content
+    pub undelete_grace: Duration,
+    /// Time after which deleted content is removed for good
+    pub retention: Duration,
+    /// Time between two runs of the purge job
+    pub interval: Duration,
+}
+
+impl RetentionPolicy {
+    /// Read from `UNDELETE_GRACE_HOURS` (24 unless set), `DELETED_CONTENT_RETENTION_DAYS`
+    /// (30 unless set) and `PURGE_INTERVAL_MINUTES` (60 unless set)
+    pub fn from_env() -> RetentionPolicy {
+        let var = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .filter(|value| *value > 0)
+                .unwrap_or(default)
+        };
+
+        RetentionPolicy {
+            undelete_grace: Duration::hours(var("UNDELETE_GRACE_HOURS", 24)),
+            retention: Duration::days(var("DELETED_CONTENT_RETENTION_DAYS", 30)),
+            interval: Duration::minutes(var("PURGE_INTERVAL_MINUTES", 60)),
+        }
+    }
+
+    /// Moderators can undelete anything until it is purged, authors only what they deleted
+    /// themselves and only within the grace period
+    pub fn check_undelete(&self, deletion: &Deletion, session: &Session) -> Result<(), Error> {
+        if session.has_role(Role::Moderator) {
+            return Ok(());
+        }
+        if deletion.account_id.as_ref() != Some(&session.account_id)
+            || deletion.deleted_by.as_ref() != Some(&session.account_id)
+        {
+            return Err(Error::Unauthorized);
+        }
+        if deletion.deleted_at + self.undelete_grace < Utc::now() {
+            return Err(Error::UndeleteWindowExpired);
+        }
+        Ok(())
+    }
+}
+
+/// Purge deleted content older than the retention period every `interval`, for as long as
+/// the server runs
+pub async fn run(store: Store, policy: RetentionPolicy) {
+    let mut interval = tokio::time::interval(
+        policy
+            .interval
+            .to_std()
+            .unwrap_or(std::time::Duration::from_secs(3600)),
+    );
+
+    loop {
+        interval.tick().await;
+        match store.purge_deleted(Utc::now() - policy.retention).await {
+            Ok((0, 0)) => {}
+            Ok((questions, answers)) => tracing::event!(
+                tracing::Level::INFO,
+                questions,
+                answers,
+                "Purged deleted content"
+            ),
+            // Logged by the store, the next run tries again
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::account::AccountId;
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy {
+            undelete_grace: Duration::hours(24),
+            retention: Duration::days(30),
+            interval: Duration::minutes(60),
+        }
+    }
+
+    fn session(account_id: i32, role: Role) -> Session {
+        Session {
+            exp: Utc::now() + Duration::days(1),
+            account_id: AccountId(account_id),
+            nbf: Utc::now(),
+            jti: None,
+            role,
+            scopes: None,
+        }
+    }
+
+    fn deletion(hours_ago: i64, deleted_by: i32, author: i32) -> Deletion {
+        Deletion {
+            deleted_at: Utc::now() - Duration::hours(hours_ago),
+            deleted_by: Some(AccountId(deleted_by)),
+            account_id: Some(AccountId(author)),
+        }
+    }
+
+    #[test]
+    fn authors_undelete_their_own_deletions_within_the_grace_period() {
+        assert!(policy()
+            .check_undelete(&deletion(1, 7, 7), &session(7, Role::User))
+            .is_ok());
+    }
+
+    #[test]
+    fn authors_cannot_undelete_after_the_grace_period() {
+        assert!(matches!(
+            policy().check_undelete(&deletion(25, 7, 7), &session(7, Role::User)),
+            Err(Error::UndeleteWindowExpired)
+        ));
+    }
+
+    #[test]
+    fn authors_cannot_undelete_what_a_moderator_deleted() {
+        assert!(matches!(
+            policy().check_undelete(&deletion(1, 3, 7), &session(7, Role::User)),
+            Err(Error::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn others_cannot_undelete() {
+        assert!(matches!(
+            policy().check_undelete(&deletion(1, 7, 7), &session(8, Role::User)),
+            Err(Error::Unauthorized)
+        ));
+
+        let anonymized = Deletion {
+            account_id: None,
+            ..deletion(1, 7, 7)
+        };
+        assert!(matches!(
+            policy().check_undelete(&anonymized, &session(7, Role::User)),
+            Err(Error::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn moderators_undelete_anything_until_it_is_purged() {
+        for role in [Role::Moderator, Role::Admin] {
+            assert!(policy()
+                .check_undelete(&deletion(24 * 29, 7, 7), &session(3, role))
+                .is_ok());
+        }
+    }
+}
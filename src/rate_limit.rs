@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use warp::filters::BoxedFilter;
+use warp::http::header::HeaderValue;
+use warp::{Filter, Reply};
+
+use crate::routes::authentication::{client_ip, extract_token, verify_token};
+use handle_errors::Error;
+
+/// Buckets kept before the ones that refilled completely get dropped
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// Who a bucket of tokens belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Account(i32),
+    Ip(IpAddr),
+    /// Requests without a valid token nor a known remote address share one bucket
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+/// Token bucket rate limiter, every client starts with `capacity` requests which
+/// refill completely over `period`
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    capacity: u32,
+    period: Duration,
+    buckets: Arc<Mutex<HashMap<Client, Bucket>>>,
+}
+
+/// State of the bucket of the client after taking a request out of it
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the bucket is full again
+    pub reset: u64,
+}
+
+impl RateLimiter {
+    pub fn new(capacity: u32, period: Duration) -> Self {
+        RateLimiter {
+            capacity,
+            period,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Take a request out of the bucket of the client, rejecting it once the bucket is empty.
+    ///
+    /// Attach it after the path and method filters of a route so requests for other routes
+    /// don't use up tokens, and end the route with `status` and `with_headers`.
+    pub fn limit(&self) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+        let limiter = self.clone();
+
+        client()
+            .and_then(move |client: Client| {
+                let limiter = limiter.clone();
+                async move { limiter.take(client).map_err(warp::reject::custom) }
+            })
+            .untuple_one()
+    }
+
+    /// State of the bucket of the client, without taking a request out of it
+    pub fn status(
+        &self,
+    ) -> impl Filter<Extract = (RateLimitStatus,), Error = warp::Rejection> + Clone {
+        let limiter = self.clone();
+
+        client().map(move |client: Client| limiter.update(client, |_| {}))
+    }
+
+    fn take(&self, client: Client) -> Result<(), Error> {
+        let mut limited = None;
+        self.update(client.clone(), |bucket| {
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+            } else {
+                limited = Some(bucket.tokens);
+            }
+        });
+
+        match limited {
+            None => Ok(()),
+            Some(tokens) => {
+                let retry_after = self.seconds_until(tokens, 1.0);
+                tracing::event!(
+                    tracing::Level::WARN,
+                    client = ?client,
+                    "Rate limited, retry in {} seconds",
+                    retry_after
+                );
+                Err(Error::RateLimited {
+                    limit: self.capacity,
+                    reset: self.seconds_until(tokens, f64::from(self.capacity)),
+                    retry_after,
+                })
+            }
+        }
+    }
+
+    /// Refill the bucket of the client for the time elapsed since it was last used, then apply `f`
+    fn update(&self, client: Client, f: impl FnOnce(&mut Bucket)) -> RateLimitStatus {
+        let capacity = f64::from(self.capacity);
+        let per_second = capacity / self.period.as_secs_f64();
+        let now = Instant::now();
+
+        let mut buckets = self.buckets.lock().expect("rate limit buckets poisoned");
+        if buckets.len() > MAX_IDLE_BUCKETS {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+                bucket.tokens + elapsed * per_second < capacity
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: capacity,
+            refilled_at: now,
+        });
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.refilled_at = now;
+        f(bucket);
+
+        RateLimitStatus {
+            limit: self.capacity,
+            remaining: bucket.tokens.floor() as u32,
+            reset: self.seconds_until(bucket.tokens, capacity),
+        }
+    }
+
+    fn seconds_until(&self, tokens: f64, target: f64) -> u64 {
+        let per_second = f64::from(self.capacity) / self.period.as_secs_f64();
+        ((target - tokens).max(0.0) / per_second).ceil() as u64
+    }
+}
+
+/// Authenticated clients are limited per account, anonymous ones per address
+fn client() -> BoxedFilter<(Client,)> {
+    warp::header::optional::<String>("Authorization")
+        .and(client_ip())
+        .map(|header: Option<String>, ip: Option<IpAddr>| {
+            match extract_token(header).and_then(verify_token).ok() {
+                Some(session) => Client::Account(session.account_id.0),
+                None => ip.map(Client::Ip).unwrap_or(Client::Unknown),
+            }
+        })
+        .boxed()
+}
+
+/// Add the `X-RateLimit-*` headers to the reply of a rate limited route
+pub fn with_headers(reply: impl Reply, status: RateLimitStatus) -> warp::reply::Response {
+    let mut response = reply.into_response();
+    let headers = response.headers_mut();
+    headers.insert("x-ratelimit-limit", HeaderValue::from(status.limit));
+    headers.insert("x-ratelimit-remaining", HeaderValue::from(status.remaining));
+    headers.insert("x-ratelimit-reset", HeaderValue::from(status.reset));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_requests_until_the_bucket_is_empty() {
+        let limiter = RateLimiter::new(3, Duration::from_secs(60));
+        let client = Client::Account(1);
+
+        for _ in 0..3 {
+            assert!(limiter.take(client.clone()).is_ok());
+        }
+
+        match limiter.take(client.clone()) {
+            Err(Error::RateLimited {
+                limit,
+                reset,
+                retry_after,
+            }) => {
+                assert_eq!(limit, 3);
+                assert_eq!(retry_after, 20);
+                assert_eq!(reset, 60);
+            }
+            other => panic!("expected RateLimited, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn clients_have_their_own_buckets() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+
+        assert!(limiter.take(Client::Account(1)).is_ok());
+        assert!(limiter.take(Client::Account(1)).is_err());
+        assert!(limiter.take(Client::Account(2)).is_ok());
+        assert!(limiter.take(Client::Ip([127, 0, 0, 1].into())).is_ok());
+        assert!(limiter.take(Client::Unknown).is_ok());
+    }
+
+    #[test]
+    fn status_does_not_take_a_request() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let client = Client::Account(1);
+
+        let status = limiter.update(client.clone(), |_| {});
+        assert_eq!((status.limit, status.remaining, status.reset), (2, 2, 0));
+
+        limiter.take(client.clone()).unwrap();
+        let status = limiter.update(client, |_| {});
+        assert_eq!((status.remaining, status.reset), (1, 30));
+    }
+
+    #[test]
+    fn buckets_refill_over_the_period() {
+        let limiter = RateLimiter::new(2, Duration::from_millis(100));
+        let client = Client::Account(1);
+
+        limiter.take(client.clone()).unwrap();
+        limiter.take(client.clone()).unwrap();
+        assert!(limiter.take(client.clone()).is_err());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.take(client).is_ok());
+    }
+}
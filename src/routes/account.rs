@@ -0,0 +1,100 @@
+use chrono::Utc;
+use tracing::{instrument, Level};
+use warp::http::StatusCode;
+
+use crate::routes::authentication::verify_password;
+use crate::store::Store;
+use crate::types::account::{
+    AccountDeletion, AccountId, AuthMethod, DeletionPolicy, ProfileUpdate, Session,
+};
+
+/// How recent the login of an account without a password has to be to delete it
+const REAUTHENTICATION_MINUTES: i64 = 10;
+
+pub async fn get_me(session: Session, store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_profile(&session.account_id).await {
+        Ok(profile) => Ok(warp::reply::json(&profile)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn update_me(
+    session: Session,
+    store: Store,
+    update: ProfileUpdate,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    update.validate()?;
+
+    match store.update_profile(&session.account_id, update).await {
+        Ok(profile) => Ok(warp::reply::json(&profile)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn delete_me(
+    session: Session,
+    store: Store,
+    policy: DeletionPolicy,
+    deletion: AccountDeletion,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+
+    if account.password.is_empty() {
+        // Without a password, logging in with the identity provider again is the confirmation.
+        // Refreshing the token doesn't count, so it's checked on the session row and not `nbf`
+        let stored = match &session.jti {
+            Some(jti) => store.get_session(jti).await?,
+            None => None,
+        };
+        let recent = stored.is_some_and(|stored| {
+            stored.auth_method == AuthMethod::Oidc
+                && Utc::now() - stored.authenticated_on
+                    <= chrono::Duration::minutes(REAUTHENTICATION_MINUTES)
+        });
+        if !recent {
+            return Err(warp::reject::custom(
+                handle_errors::Error::ReauthenticationRequired,
+            ));
+        }
+    } else {
+        let password = deletion.password.unwrap_or_default();
+        match verify_password(&account.password, password.as_bytes()) {
+            Ok(true) => {}
+            Ok(false) => return Err(warp::reject::custom(handle_errors::Error::WrongPassword)),
+            Err(e) => {
+                return Err(warp::reject::custom(
+                    handle_errors::Error::ArgonLibraryError(e),
+                ))
+            }
+        }
+    }
+
+    match store.delete_account(&session.account_id, policy).await {
+        Ok(_) => Ok(warp::reply::with_status("Account Deleted", StatusCode::OK)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn export_me(
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.export_account(&session.account_id).await {
+        Ok(export) => Ok(warp::reply::with_header(
+            warp::reply::json(&export),
+            "content-disposition",
+            "attachment; filename=\"account-export.json\"",
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+#[instrument]
+pub async fn get_user(id: i32, store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying User {}", id);
+
+    match store.get_public_profile(&AccountId(id)).await {
+        Ok(profile) => Ok(warp::reply::json(&profile)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
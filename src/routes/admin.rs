@@ -0,0 +1,46 @@
+use std::collections::HashMap;
+use tracing::{instrument, Level};
+
+use crate::store::Store;
+use crate::types::account::{AccountId, RoleChange, Session};
+use crate::types::pagination::extract_pagination;
+
+#[instrument]
+pub async fn get_accounts(
+    params: HashMap<String, String>,
+    _session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying Accounts");
+    let pagination = extract_pagination(&params)?;
+
+    match store
+        .get_accounts(pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(accounts) => Ok(warp::reply::json(&accounts)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn set_account_role(
+    id: i32,
+    session: Session,
+    store: Store,
+    change: RoleChange,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = AccountId(id);
+    let account = store.set_account_role(&account_id, change.role).await?;
+    tracing::event!(
+        Level::INFO,
+        admin = session.account_id.0,
+        account = id,
+        role = change.role.as_str(),
+        "Account role changed"
+    );
+
+    // The role is carried by the tokens, force a new login so it takes effect
+    store.revoke_all_sessions(&account_id).await?;
+
+    Ok(warp::reply::json(&account))
+}
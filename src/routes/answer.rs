@@ -1,15 +1,60 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{instrument, Level};
+use warp::http::header::ETAG;
 use warp::http::StatusCode;
 
+use crate::content_filter::ContentModerator;
+use crate::purge::RetentionPolicy;
 use crate::store::Store;
-use crate::types::account::Session;
-use crate::types::answer::NewAnswer;
+use crate::types::account::{Role, Session};
+use crate::types::answer::{Answer, NewAnswer};
+use crate::types::moderation::ContentKind;
+use crate::types::pagination::extract_pagination;
+use crate::types::version::{etag, IfMatch};
+use crate::types::vote::NewVote;
+
+#[instrument]
+pub async fn get_answers(
+    question_id: i32,
+    params: HashMap<String, String>,
+    session: Option<Session>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(
+        target: "warp_exp",
+        Level::INFO,
+        "Querying Answers of Question {}",
+        question_id
+    );
+    let pagination = extract_pagination(&params)?;
+
+    let mut res: Vec<Answer> = match store
+        .get_answers(question_id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if let Some(session) = session {
+        store
+            .set_answer_viewers(&mut res, &session.account_id)
+            .await?;
+    }
+    Ok(warp::reply::json(&res))
+}
 
 pub async fn add_answer(
     session: Session,
     store: Store,
+    moderator: Arc<ContentModerator>,
     params: NewAnswer,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let account_id = session.account_id;
+    let params = NewAnswer {
+        content: moderator.moderate("content", params.content).await?,
+        ..params
+    };
 
     if let Err(e) = store.add_answer(params, account_id).await {
         return Err(warp::reject::custom(e));
@@ -20,3 +65,158 @@ pub async fn add_answer(
         StatusCode::CREATED,
     ))
 }
+
+pub async fn update_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    moderator: Arc<ContentModerator>,
+    if_match: Option<String>,
+    answer: Answer,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
+
+    // Moderators can change any answer, not only their own
+    if session.has_role(Role::Moderator) || store.is_answer_owner(id, &account_id).await? {
+        let answer = Answer {
+            content: moderator.moderate("content", answer.content).await?,
+            ..answer
+        };
+        let res = match store
+            .update_answer(answer, id, &account_id, &if_match)
+            .await
+        {
+            Ok(res) => res,
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+        Ok(warp::reply::with_header(
+            warp::reply::json(&res),
+            ETAG,
+            etag(res.version),
+        ))
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+pub async fn delete_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    if_match: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
+    // Moderators can change any answer, not only their own
+    if session.has_role(Role::Moderator) || store.is_answer_owner(id, &account_id).await? {
+        match store.delete_answer(id, &account_id, &if_match).await {
+            Ok(true) => {}
+            Ok(false) => return Err(warp::reject::custom(handle_errors::Error::AnswerNotFound)),
+            Err(e) => return Err(warp::reject::custom(e)),
+        };
+
+        Ok(warp::reply::with_status(
+            format!("Answer {} Deleted", id),
+            StatusCode::OK,
+        ))
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/// Every revision of the answer with a diff from the one before it
+pub async fn get_answer_revisions(
+    id: i32,
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pagination = extract_pagination(&params)?;
+
+    match store
+        .get_answer_revisions(id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(revisions) => Ok(warp::reply::json(&revisions)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Restore the answer to an earlier revision, recorded as a new revision
+pub async fn rollback_answer(
+    id: i32,
+    revision: i32,
+    session: Session,
+    store: Store,
+    if_match: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
+
+    // Moderators can change any answer, not only their own
+    if session.has_role(Role::Moderator) || store.is_answer_owner(id, &account_id).await? {
+        match store
+            .rollback_answer(id, revision, &account_id, &if_match)
+            .await
+        {
+            Ok(answer) => Ok(warp::reply::with_header(
+                warp::reply::json(&answer),
+                ETAG,
+                etag(answer.version),
+            )),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/// Bring back a soft deleted answer, see `RetentionPolicy::check_undelete` for who may
+pub async fn undelete_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    policy: RetentionPolicy,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let deletion = store
+        .get_deletion(ContentKind::Answer, id)
+        .await?
+        .ok_or(handle_errors::Error::NotDeleted)?;
+    policy.check_undelete(&deletion, &session)?;
+
+    if !store.undelete(ContentKind::Answer, id).await? {
+        // Undeleted by someone else in the meantime
+        return Err(warp::reject::custom(handle_errors::Error::NotDeleted));
+    }
+
+    Ok(warp::reply::with_status(
+        format!("Answer {} Undeleted", id),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn vote_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store
+        .vote_answer(id, session.account_id, Some(vote.vote))
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn retract_answer_vote(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.vote_answer(id, session.account_id, None).await {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,63 @@
+use uuid::Uuid;
+use warp::http::StatusCode;
+
+use crate::routes::authentication::{generate_secret, hash_password, API_KEY_PREFIX};
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::api_key::{CreatedApiKey, NewApiKey};
+
+pub async fn create_api_key(
+    session: Session,
+    store: Store,
+    new_key: NewApiKey,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    new_key.validate()?;
+
+    let id = Uuid::new_v4().to_string();
+    let secret = generate_secret();
+    let api_key = store
+        .add_api_key(
+            &id,
+            &session.account_id,
+            &new_key,
+            hash_password(secret.as_bytes()),
+        )
+        .await?;
+
+    tracing::event!(
+        tracing::Level::INFO,
+        account = session.account_id.0,
+        api_key = %id,
+        "API key created"
+    );
+
+    Ok(warp::reply::with_status(
+        warp::reply::json(&CreatedApiKey {
+            api_key,
+            key: format!("{}{}.{}", API_KEY_PREFIX, id, secret),
+        }),
+        StatusCode::CREATED,
+    ))
+}
+
+pub async fn get_api_keys(
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.get_api_keys(&session.account_id, false).await {
+        Ok(api_keys) => Ok(warp::reply::json(&api_keys)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn revoke_api_key(
+    id: String,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !store.revoke_api_key(&id, &session.account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::ApiKeyNotFound));
+    }
+
+    Ok(warp::reply::with_status("API Key Revoked", StatusCode::OK))
+}
@@ -1,12 +1,51 @@
 use argon2::{self, Config};
 use chrono::prelude::*;
+use paseto::errors::GenericError;
 use rand::Rng;
-use std::future;
 use warp::{http::StatusCode, Filter};
 
+use crate::mailer::{Email, Mailer};
 use crate::store::Store;
-use crate::types::account::{Account, AccountId, Session};
+use crate::types::account::{
+    is_valid_email, Account, AccountId, AuthMethod, AuthTokens, LoginAttemptKind, PasswordChange,
+    PasswordResetConfirm, PasswordResetRequest, RefreshRequest, ResendVerification, Role, Session,
+    VerifyEmail,
+};
+use crate::types::api_key::ApiKeyScope;
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// How long a refresh token can be traded for new tokens
+const REFRESH_TOKEN_DAYS: i64 = 30;
+
+/// How long an emailed password reset token stays valid
+const PASSWORD_RESET_MINUTES: i64 = 60;
+
+/// How long an emailed verification token stays valid
+const EMAIL_VERIFICATION_HOURS: i64 = 48;
+
+/// Marks an API key in the `Authorization` header, the rest is `<id>.<secret>`
+pub const API_KEY_PREFIX: &str = "qa_";
+
+/// Failed logins older than this are forgotten
+const LOGIN_FAILURE_WINDOW_MINUTES: i64 = 60;
+
+/// Failed logins allowed before an email address gets locked
+const MAX_FAILED_LOGINS_PER_EMAIL: i32 = 5;
+
+/// Failed logins allowed before a client IP gets locked, higher since IPs can be shared
+const MAX_FAILED_LOGINS_PER_IP: i32 = 20;
+
+/// First lockout, doubled with every further failure
+const LOGIN_LOCKOUT_BASE_SECONDS: i64 = 30;
+
+const LOGIN_LOCKOUT_MAX_SECONDS: i64 = 15 * 60;
+
+/// Hash checked for unknown email addresses, so that they take as long to reject as a
+/// wrong password. Made with the same `Config` as `hash_password`, of a discarded secret.
+const DUMMY_PASSWORD_HASH: &str = "$argon2i$v=19$m=4096,t=3,p=1$yNboOuXr7OMBHnV25P3TNdzDi1XfxugSpES0/CEz4ic$uwMvZUh1zDoXFZ+R2flYhRSKnpRFP9JsdChAGB12ElQ";
 
 pub fn verify_token(token: String) -> Result<Session, handle_errors::Error> {
     let key = env::var("PASETO_KEY").unwrap();
@@ -17,71 +56,607 @@ pub fn verify_token(token: String) -> Result<Session, handle_errors::Error> {
         key.as_bytes(),
         &paseto::tokens::TimeBackend::Chrono,
     )
-    .map_err(|_| handle_errors::Error::CannotDecrptToken)?;
+    .map_err(|e| match e.downcast_ref::<GenericError>() {
+        Some(GenericError::ExpiredToken {}) => handle_errors::Error::TokenExpired,
+        Some(GenericError::InvalidNotBeforeToken {} | GenericError::InvalidIssuedAtToken {}) => {
+            handle_errors::Error::TokenNotYetValid
+        }
+        _ => handle_errors::Error::CannotDecrptToken,
+    })?;
 
     serde_json::from_value::<Session>(token).map_err(|_| handle_errors::Error::CannotDecrptToken)
 }
 
-pub fn auth() -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization").and_then(|token: String| {
-        let token = match verify_token(token) {
-            Ok(t) => t,
-            Err(_) => return future::ready(Err(warp::reject::reject())),
-        };
+/// Token of an `Authorization` header value, either `Bearer <token>` or the bare token
+/// clients sent before the bearer scheme was supported
+pub fn extract_token(header: Option<String>) -> Result<String, handle_errors::Error> {
+    let header = header.ok_or(handle_errors::Error::MissingToken)?;
+    let header = header.trim();
+
+    match header.split_once(char::is_whitespace) {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+            let token = token.trim();
+            if token.is_empty() || token.contains(char::is_whitespace) {
+                Err(handle_errors::Error::MalformedToken)
+            } else {
+                Ok(token.to_string())
+            }
+        }
+        Some(_) => Err(handle_errors::Error::MalformedToken),
+        None if header.is_empty() || header.eq_ignore_ascii_case("bearer") => {
+            Err(handle_errors::Error::MalformedToken)
+        }
+        None => Ok(header.to_string()),
+    }
+}
+
+/// Authenticate the request with a token from `/login`. API keys are turned away,
+/// routes open to them use `require_scope()`
+pub fn auth(store: Store) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("Authorization").and_then(move |header: Option<String>| {
+        let store = store.clone();
+        async move {
+            authenticate(&store, header, None)
+                .await
+                .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// Like `auth()`, but also accepts API keys holding `scope`
+pub fn require_scope(
+    store: Store,
+    scope: ApiKeyScope,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("Authorization").and_then(move |header: Option<String>| {
+        let store = store.clone();
+        async move {
+            authenticate(&store, header, Some(scope))
+                .await
+                .map_err(warp::reject::custom)
+        }
+    })
+}
+
+/// Like `auth()`, but lets requests without an `Authorization` header through anonymously.
+/// Expired, revoked or otherwise unusable credentials are treated as anonymous too, so a stale
+/// token never locks a client out of public reads.
+pub fn optional_auth(
+    store: Store,
+) -> impl Filter<Extract = (Option<Session>,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("Authorization").and_then(move |header: Option<String>| {
+        let store = store.clone();
+        async move {
+            if header.is_none() {
+                return Ok(None);
+            }
+
+            match authenticate(&store, header, Some(ApiKeyScope::Read)).await {
+                Ok(session) => Ok(Some(session)),
+                Err(e @ handle_errors::Error::DatabaseQueryError(_)) => {
+                    Err(warp::reject::custom(e))
+                }
+                Err(_) => Ok(None),
+            }
+        }
+    })
+}
+
+/// Turn the `Authorization` header into a session, API keys are only accepted when they
+/// hold `scope`
+async fn authenticate(
+    store: &Store,
+    header: Option<String>,
+    scope: Option<ApiKeyScope>,
+) -> Result<Session, handle_errors::Error> {
+    let token = extract_token(header)?;
+
+    let session = match token.strip_prefix(API_KEY_PREFIX) {
+        Some(api_key) => api_key_session(store, api_key).await?,
+        None => {
+            let session = verify_token(token)?;
+
+            // Tokens outlive a logout, so the session they belong to must still be active
+            if !store.is_session_active(&session).await? {
+                return Err(handle_errors::Error::SessionRevoked);
+            }
+            session
+        }
+    };
+
+    if !store.is_email_verified(&session.account_id).await? {
+        return Err(handle_errors::Error::EmailNotVerified);
+    }
+
+    let allowed = match scope {
+        Some(scope) => session.has_scope(scope),
+        None => session.scopes.is_none(),
+    };
+    if !allowed {
+        return Err(handle_errors::Error::InsufficientScope);
+    }
+
+    Ok(session)
+}
+
+/// Session of a request authenticated with the `<id>.<secret>` part of an API key
+async fn api_key_session(store: &Store, api_key: &str) -> Result<Session, handle_errors::Error> {
+    let (id, secret) = api_key
+        .split_once('.')
+        .ok_or(handle_errors::Error::InvalidApiKey)?;
+
+    let stored = match store.get_api_key(id).await? {
+        Some(stored) if !stored.revoked => stored,
+        _ => return Err(handle_errors::Error::InvalidApiKey),
+    };
+
+    if !verify_password(&stored.key_hash, secret.as_bytes())
+        .map_err(handle_errors::Error::ArgonLibraryError)?
+    {
+        return Err(handle_errors::Error::InvalidApiKey);
+    }
 
-        // and_then method in Warp expects a closure that returns a Future.
-        // future::ready will wrap the `Result` in a `Ready` future that resolves immediately to
-        // that value.
-        future::ready(Ok(token))
+    store.touch_api_key(&stored.id).await?;
+    let role = store.get_account_role(&stored.account_id).await?;
+
+    // API keys don't expire, the session only lasts for the request
+    Ok(Session {
+        exp: Utc::now(),
+        account_id: stored.account_id,
+        nbf: stored.created_on,
+        jti: None,
+        role,
+        scopes: Some(stored.scopes),
     })
 }
 
-pub async fn register(store: Store, account: Account) -> Result<impl warp::Reply, warp::Rejection> {
+/// Like `auth()`, but also rejects sessions whose role is below `role`
+pub fn require_role(
+    store: Store,
+    role: Role,
+) -> impl Filter<Extract = (Session,), Error = warp::Rejection> + Clone {
+    auth(store).and_then(move |session: Session| async move {
+        if session.has_role(role) {
+            Ok(session)
+        } else {
+            Err(warp::reject::custom(handle_errors::Error::InsufficientRole))
+        }
+    })
+}
+
+pub async fn register(
+    store: Store,
+    mailer: Arc<dyn Mailer>,
+    account: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    if !is_valid_email(&account.email) {
+        return Err(warp::reject::custom(
+            handle_errors::Error::InvalidParameter("email".to_string()),
+        ));
+    }
+
     let hashed_password = hash_password(account.password.as_bytes());
     let account = Account {
         id: account.id,
         email: account.email,
         password: hashed_password,
     };
+    let email = account.email.clone();
 
-    match store.add_account(account).await {
-        Ok(_) => Ok(warp::reply::with_status("Account Added", StatusCode::OK)),
+    match store.clone().add_account(account).await {
+        Ok(account_id) => {
+            send_verification_email(&store, mailer.as_ref(), &account_id, email).await?;
+            Ok(warp::reply::with_status(
+                "Account Added, check your emails to verify it",
+                StatusCode::OK,
+            ))
+        }
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
 
-pub async fn login(store: Store, login: Account) -> Result<impl warp::Reply, warp::Rejection> {
-    match store.get_account(login.email).await {
-        Ok(account) => {
-            println!("Account {:?}", account);
-            match verify_password(&account.password, login.password.as_bytes()) {
-                Ok(verified) => {
-                    if verified {
-                        Ok(warp::reply::json(&issue_token(
-                            account.id.expect("id not found"),
-                        )))
-                    } else {
-                        Err(warp::reject::custom(handle_errors::Error::WrongPassword))
-                    }
-                }
-                Err(e) => Err(warp::reject::custom(
-                    handle_errors::Error::ArgonLibraryError(e),
-                )),
+pub async fn verify_email(
+    store: Store,
+    request: VerifyEmail,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let (id, secret) = request
+        .token
+        .split_once('.')
+        .ok_or(handle_errors::Error::InvalidVerificationToken)?;
+
+    let verification = match store.get_email_verification(id).await? {
+        Some(verification) if !verification.used && verification.expires_on > Utc::now() => {
+            verification
+        }
+        _ => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::InvalidVerificationToken,
+            ))
+        }
+    };
+
+    if !verify_password(&verification.token_hash, secret.as_bytes())
+        .map_err(handle_errors::Error::ArgonLibraryError)?
+        || !store.verify_email(&verification).await?
+    {
+        return Err(warp::reject::custom(
+            handle_errors::Error::InvalidVerificationToken,
+        ));
+    }
+
+    Ok(warp::reply::with_status("Email Verified", StatusCode::OK))
+}
+
+/// Send a new verification email, replies the same whether the account exists or not
+pub async fn resend_verification(
+    store: Store,
+    mailer: Arc<dyn Mailer>,
+    request: ResendVerification,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let reply = warp::reply::with_status(
+        "If the account needs it a verification email has been sent",
+        StatusCode::ACCEPTED,
+    );
+
+    let account = match store.get_account(request.email).await {
+        Ok(account) => account,
+        Err(handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            return Ok(reply)
+        }
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    let account_id = account.id.expect("id not found");
+
+    if !store.is_email_verified(&account_id).await? {
+        send_verification_email(&store, mailer.as_ref(), &account_id, account.email).await?;
+    }
+
+    Ok(reply)
+}
+
+async fn send_verification_email(
+    store: &Store,
+    mailer: &dyn Mailer,
+    account_id: &AccountId,
+    email: String,
+) -> Result<(), handle_errors::Error> {
+    let id = Uuid::new_v4().to_string();
+    let secret = generate_secret();
+    let expires_on = Utc::now() + chrono::Duration::hours(EMAIL_VERIFICATION_HOURS);
+    store
+        .add_email_verification(
+            &id,
+            account_id,
+            hash_password(secret.as_bytes()),
+            expires_on,
+        )
+        .await?;
+
+    mailer
+        .send(Email {
+            to: email,
+            subject: "Verify your email address".to_string(),
+            body: format!(
+                "Use the following token to verify your email address, it expires in {} hours:\n\n{}.{}",
+                EMAIL_VERIFICATION_HOURS, id, secret
+            ),
+        })
+        .await
+}
+
+/// Address of the client, taken from `X-Forwarded-For` when `TRUST_FORWARDED_FOR` is set
+/// because the service runs behind a proxy
+pub fn client_ip() -> impl Filter<Extract = (Option<IpAddr>,), Error = warp::Rejection> + Clone {
+    let trust_forwarded_for = env::var("TRUST_FORWARDED_FOR").is_ok();
+
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .map(
+            move |remote: Option<SocketAddr>, forwarded_for: Option<String>| {
+                let forwarded = forwarded_for
+                    .filter(|_| trust_forwarded_for)
+                    .and_then(|value| value.split(',').next()?.trim().parse::<IpAddr>().ok());
+                forwarded.or_else(|| remote.map(|addr| addr.ip()))
+            },
+        )
+}
+
+pub async fn login(
+    store: Store,
+    client_ip: Option<IpAddr>,
+    login: Account,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let mut attempt_keys = vec![(LoginAttemptKind::Email, login.email.to_lowercase())];
+    if let Some(ip) = client_ip {
+        attempt_keys.push((LoginAttemptKind::Ip, ip.to_string()));
+    }
+
+    if let Some(locked_until) = store.get_login_lockout(&attempt_keys).await? {
+        let seconds = (locked_until - Utc::now()).num_seconds().max(1) as u64;
+        return Err(warp::reject::custom(
+            handle_errors::Error::TooManyLoginAttempts(seconds),
+        ));
+    }
+
+    let account = match store.get_account(login.email).await {
+        Ok(account) => account,
+        Err(e) => {
+            if !matches!(
+                e,
+                handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)
+            ) {
+                return Err(warp::reject::custom(e));
             }
+
+            // Unknown addresses fail exactly like a wrong password
+            let _ = verify_password(DUMMY_PASSWORD_HASH, login.password.as_bytes());
+            record_login_failure(&store, &attempt_keys).await?;
+            return Err(warp::reject::custom(handle_errors::Error::WrongPassword));
+        }
+    };
+
+    match verify_password(&account.password, login.password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => {
+            record_login_failure(&store, &attempt_keys).await?;
+            return Err(warp::reject::custom(handle_errors::Error::WrongPassword));
+        }
+        Err(e) => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::ArgonLibraryError(e),
+            ))
         }
-        Err(e) => Err(warp::reject::custom(e)),
     }
+
+    // Only the email address is cleared, a shared IP keeps counting towards its own limit
+    store
+        .clear_login_failures(LoginAttemptKind::Email, &attempt_keys[0].1)
+        .await?;
+
+    let account_id = account.id.clone().expect("id not found");
+    if !store.is_email_verified(&account_id).await? {
+        return Err(warp::reject::custom(handle_errors::Error::EmailNotVerified));
+    }
+
+    let tokens = start_session(&store, account_id, AuthMethod::Password, Utc::now()).await?;
+    Ok(warp::reply::json(&tokens))
 }
 
-pub async fn reset_password(
+/// Count a failed login against the email address and client IP, locking them out
+/// with an exponential backoff once they are over their limit
+async fn record_login_failure(
+    store: &Store,
+    attempt_keys: &[(LoginAttemptKind, String)],
+) -> Result<(), handle_errors::Error> {
+    let window_start = Utc::now() - chrono::Duration::minutes(LOGIN_FAILURE_WINDOW_MINUTES);
+
+    for (kind, key) in attempt_keys {
+        let failures = store.add_login_failure(*kind, key, window_start).await?;
+        let allowed = match kind {
+            LoginAttemptKind::Email => MAX_FAILED_LOGINS_PER_EMAIL,
+            LoginAttemptKind::Ip => MAX_FAILED_LOGINS_PER_IP,
+        };
+        if failures < allowed {
+            continue;
+        }
+
+        let doublings = (failures - allowed).min(16) as u32;
+        let seconds = (LOGIN_LOCKOUT_BASE_SECONDS << doublings).min(LOGIN_LOCKOUT_MAX_SECONDS);
+        store
+            .lock_login(*kind, key, Utc::now() + chrono::Duration::seconds(seconds))
+            .await?;
+
+        tracing::event!(
+            tracing::Level::WARN,
+            kind = kind.as_str(),
+            key = %key,
+            failures,
+            "Login locked for {} seconds",
+            seconds
+        );
+    }
+
+    Ok(())
+}
+
+/// Trade a refresh token for a new pair of tokens, the old refresh token stops working
+pub async fn refresh_token(
     store: Store,
-    account: Account,
+    request: RefreshRequest,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let hashed_password = hash_password(account.password.as_bytes());
-    match store.reset_password(account, hashed_password).await {
-        Ok(_) => Ok(warp::reply::with_status("Password Changed", StatusCode::OK)),
-        Err(e) => Err(warp::reject::custom(e)),
+    let (jti, secret) = request
+        .refresh_token
+        .split_once('.')
+        .ok_or(handle_errors::Error::InvalidRefreshToken)?;
+
+    let stored = match store.get_session(jti).await? {
+        Some(stored) if stored.expires_on > Utc::now() => stored,
+        _ => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::InvalidRefreshToken,
+            ))
+        }
+    };
+
+    if !verify_password(&stored.refresh_token_hash, secret.as_bytes())
+        .map_err(handle_errors::Error::ArgonLibraryError)?
+    {
+        return Err(warp::reject::custom(
+            handle_errors::Error::InvalidRefreshToken,
+        ));
+    }
+
+    if !store.revoke_session(&stored.id).await? {
+        // A rotated refresh token is used again, someone else may hold a copy of it
+        tracing::event!(
+            tracing::Level::WARN,
+            account_id = stored.account_id.0,
+            "Refresh token reused, revoking every session"
+        );
+        store.revoke_all_sessions(&stored.account_id).await?;
+        return Err(warp::reject::custom(
+            handle_errors::Error::InvalidRefreshToken,
+        ));
     }
+
+    let tokens = start_session(
+        &store,
+        stored.account_id,
+        stored.auth_method,
+        stored.authenticated_on,
+    )
+    .await?;
+    Ok(warp::reply::json(&tokens))
+}
+
+pub async fn logout(session: Session, store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+    match session.jti {
+        Some(jti) => {
+            store.revoke_session(&jti).await?;
+        }
+        // Tokens without a session id can only be revoked all at once
+        None => store.revoke_all_sessions(&session.account_id).await?,
+    }
+
+    Ok(warp::reply::with_status("Logged out", StatusCode::OK))
+}
+
+pub async fn logout_everywhere(
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    store.revoke_all_sessions(&session.account_id).await?;
+
+    Ok(warp::reply::with_status(
+        "Logged out of every session",
+        StatusCode::OK,
+    ))
+}
+
+/// Start a password reset, the token is mailed to the account and never returned here
+///
+/// The reply is the same whether the email belongs to an account or not, so this can't be used
+/// to find out which emails are registered. Looking the account up, hashing the token and
+/// sending the email happen after replying, so the timing doesn't tell either.
+pub async fn request_password_reset(
+    store: Store,
+    mailer: Arc<dyn Mailer>,
+    request: PasswordResetRequest,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tokio::spawn(async move {
+        if let Err(e) = send_password_reset(&store, mailer.as_ref(), request.email).await {
+            tracing::event!(
+                tracing::Level::ERROR,
+                "Password reset email not sent: {:?}",
+                e
+            );
+        }
+    });
+
+    Ok(warp::reply::with_status(
+        "If the account exists a reset email has been sent",
+        StatusCode::ACCEPTED,
+    ))
+}
+
+async fn send_password_reset(
+    store: &Store,
+    mailer: &dyn Mailer,
+    email: String,
+) -> Result<(), handle_errors::Error> {
+    let account = match store.get_account(email).await {
+        Ok(account) => account,
+        Err(handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let account_id = account.id.expect("id not found");
+
+    let id = Uuid::new_v4().to_string();
+    let secret = generate_secret();
+    let expires_on = Utc::now() + chrono::Duration::minutes(PASSWORD_RESET_MINUTES);
+    store
+        .add_password_reset(
+            &id,
+            &account_id,
+            hash_password(secret.as_bytes()),
+            expires_on,
+        )
+        .await?;
+
+    mailer
+        .send(Email {
+            to: account.email,
+            subject: "Reset your password".to_string(),
+            body: format!(
+                "Use the following token to choose a new password, it expires in {} minutes:\n\n{}.{}",
+                PASSWORD_RESET_MINUTES, id, secret
+            ),
+        })
+        .await
+}
+
+pub async fn confirm_password_reset(
+    store: Store,
+    confirm: PasswordResetConfirm,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let (id, secret) = confirm
+        .token
+        .split_once('.')
+        .ok_or(handle_errors::Error::InvalidResetToken)?;
+
+    let reset = match store.get_password_reset(id).await? {
+        Some(reset) if !reset.used && reset.expires_on > Utc::now() => reset,
+        _ => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::InvalidResetToken,
+            ))
+        }
+    };
+
+    if !verify_password(&reset.token_hash, secret.as_bytes())
+        .map_err(handle_errors::Error::ArgonLibraryError)?
+        || !store
+            .reset_password(
+                &reset.id,
+                &reset.account_id,
+                hash_password(confirm.password.as_bytes()),
+            )
+            .await?
+    {
+        return Err(warp::reject::custom(
+            handle_errors::Error::InvalidResetToken,
+        ));
+    }
+
+    Ok(warp::reply::with_status("Password Changed", StatusCode::OK))
+}
+
+pub async fn change_password(
+    session: Session,
+    store: Store,
+    change: PasswordChange,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account = store.get_account_by_id(&session.account_id).await?;
+
+    match verify_password(&account.password, change.old_password.as_bytes()) {
+        Ok(true) => {}
+        Ok(false) => return Err(warp::reject::custom(handle_errors::Error::WrongPassword)),
+        Err(e) => {
+            return Err(warp::reject::custom(
+                handle_errors::Error::ArgonLibraryError(e),
+            ))
+        }
+    }
+
+    store
+        .change_password(
+            &session.account_id,
+            hash_password(change.new_password.as_bytes()),
+            session.jti.as_deref(),
+        )
+        .await?;
+
+    Ok(warp::reply::with_status("Password Changed", StatusCode::OK))
 }
 
 pub fn hash_password(password: &[u8]) -> String {
@@ -91,11 +666,55 @@ pub fn hash_password(password: &[u8]) -> String {
     argon2::hash_encoded(password, &salt, &config).unwrap()
 }
 
-fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
+pub fn verify_password(hash: &str, password: &[u8]) -> Result<bool, argon2::Error> {
+    // Accounts created through an identity provider have no password to log in with
+    if hash.is_empty() {
+        return Ok(false);
+    }
     argon2::verify_encoded(hash, password)
 }
 
-fn issue_token(account_id: AccountId) -> String {
+/// Persist a new session and issue its access and refresh tokens, `auth_method` and
+/// `authenticated_on` describe the login the session goes back to
+pub async fn start_session(
+    store: &Store,
+    account_id: AccountId,
+    auth_method: AuthMethod,
+    authenticated_on: DateTime<Utc>,
+) -> Result<AuthTokens, handle_errors::Error> {
+    let jti = Uuid::new_v4().to_string();
+    let secret = generate_secret();
+    let expires_on = Utc::now() + chrono::Duration::days(REFRESH_TOKEN_DAYS);
+
+    store
+        .add_session(
+            &jti,
+            &account_id,
+            hash_password(secret.as_bytes()),
+            expires_on,
+            auth_method,
+            authenticated_on,
+        )
+        .await?;
+
+    let role = store.get_account_role(&account_id).await?;
+
+    Ok(AuthTokens {
+        token: issue_token(account_id, role, &jti),
+        refresh_token: format!("{}.{}", jti, secret),
+    })
+}
+
+/// Random secret part of refresh and reset tokens, hex encoded
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .gen::<[u8; 32]>()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn issue_token(account_id: AccountId, role: Role, jti: &str) -> String {
     let key = env::var("PASETO_KEY").unwrap();
     let current_date_time = Utc::now();
     let dt = current_date_time + chrono::Duration::days(1);
@@ -104,7 +723,9 @@ fn issue_token(account_id: AccountId) -> String {
         .set_encryption_key(&Vec::from(key.as_bytes()))
         .set_expiration(&dt)
         .set_not_before(&Utc::now())
+        .set_jti(jti)
         .set_claim("account_id", serde_json::json!(account_id))
+        .set_claim("role", serde_json::json!(role))
         .build()
         .expect("Failed to consturct paseto token w/ builder!")
 }
@@ -1,3 +1,9 @@
+pub mod account;
+pub mod admin;
 pub mod answer;
+pub mod api_key;
 pub mod authentication;
+pub mod moderation;
+pub mod oidc;
 pub mod question;
+pub mod search;
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use tracing::{instrument, Level};
+use warp::http::StatusCode;
+
+use crate::store::Store;
+use crate::types::account::Session;
+use crate::types::moderation::{
+    ContentKind, FlagThreshold, ModerationAction, ModerationNote, ModerationState, NewFlag,
+};
+use crate::types::pagination::extract_pagination;
+
+pub async fn flag_question(
+    id: i32,
+    session: Session,
+    store: Store,
+    threshold: FlagThreshold,
+    new_flag: NewFlag,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    flag(
+        ContentKind::Question,
+        id,
+        session,
+        store,
+        threshold,
+        new_flag,
+    )
+    .await
+}
+
+pub async fn flag_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    threshold: FlagThreshold,
+    new_flag: NewFlag,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    flag(ContentKind::Answer, id, session, store, threshold, new_flag).await
+}
+
+async fn flag(
+    kind: ContentKind,
+    id: i32,
+    session: Session,
+    store: Store,
+    threshold: FlagThreshold,
+    new_flag: NewFlag,
+) -> Result<warp::reply::WithStatus<&'static str>, warp::Rejection> {
+    new_flag.validate()?;
+
+    let state = store
+        .flag_content(kind, id, &session.account_id, &new_flag.reason, threshold)
+        .await?;
+    if state == ModerationState::Queued {
+        tracing::event!(
+            Level::INFO,
+            kind = kind.as_str(),
+            id,
+            "Hidden and queued for moderation"
+        );
+    }
+
+    Ok(warp::reply::with_status(
+        "Flag recorded",
+        StatusCode::CREATED,
+    ))
+}
+
+#[instrument]
+pub async fn get_queue(
+    params: HashMap<String, String>,
+    _session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying Moderation Queue");
+    let pagination = extract_pagination(&params)?;
+
+    match store
+        .get_moderation_queue(pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(queue) => Ok(warp::reply::json(&queue)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn moderate(
+    kind: ContentKind,
+    id: i32,
+    action: ModerationAction,
+    session: Session,
+    store: Store,
+    note: ModerationNote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let note = note
+        .note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+
+    let record = store
+        .moderate(kind, id, action, &session.account_id, note)
+        .await?;
+    tracing::event!(
+        Level::INFO,
+        moderator = session.account_id.0,
+        kind = kind.as_str(),
+        id,
+        action = action.as_str(),
+        "Moderation action taken"
+    );
+
+    Ok(warp::reply::json(&record))
+}
@@ -0,0 +1,109 @@
+use chrono::prelude::*;
+use std::sync::Arc;
+use warp::http::StatusCode;
+
+use crate::oidc::OidcClient;
+use crate::routes::authentication::{generate_secret, start_session};
+use crate::store::Store;
+use crate::types::account::{AccountId, AuthMethod};
+use crate::types::oidc::{ExternalIdentity, OidcCallback, PendingOidcLogin};
+
+/// How long the identity provider has to redirect back after the login started
+const OIDC_LOGIN_MINUTES: i64 = 10;
+
+/// Start a login with the identity provider by redirecting to it
+pub async fn oidc_login(
+    oidc: Option<Arc<OidcClient>>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let oidc = oidc.ok_or(handle_errors::Error::OidcNotConfigured)?;
+
+    let login = PendingOidcLogin {
+        state: generate_secret(),
+        nonce: generate_secret(),
+        code_verifier: generate_secret(),
+        expires_on: Utc::now() + chrono::Duration::minutes(OIDC_LOGIN_MINUTES),
+    };
+    let url = oidc.authorization_url(&login).await?;
+    store.add_oidc_login(&login).await?;
+
+    Ok(warp::reply::with_header(
+        warp::reply::with_status("Redirecting to the identity provider", StatusCode::FOUND),
+        "location",
+        url,
+    ))
+}
+
+/// Finish a login with the identity provider, issuing the same tokens as `/login`
+pub async fn oidc_callback(
+    oidc: Option<Arc<OidcClient>>,
+    store: Store,
+    callback: OidcCallback,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let oidc = oidc.ok_or(handle_errors::Error::OidcNotConfigured)?;
+
+    if let Some(error) = callback.error {
+        tracing::event!(
+            tracing::Level::WARN,
+            error = %error,
+            description = ?callback.error_description,
+            "Identity provider turned the login down"
+        );
+        return Err(warp::reject::custom(handle_errors::Error::InvalidOidcLogin));
+    }
+    let (code, state) = match (callback.code, callback.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(warp::reject::custom(handle_errors::Error::InvalidOidcLogin)),
+    };
+
+    let login = match store.take_oidc_login(&state).await? {
+        Some(login) if login.expires_on > Utc::now() => login,
+        _ => return Err(warp::reject::custom(handle_errors::Error::InvalidOidcLogin)),
+    };
+
+    let identity = oidc.exchange_code(&code, &login).await?;
+    let account_id = match store.get_identity_account(&identity).await? {
+        Some(account_id) => account_id,
+        None => link_identity(&store, &identity).await?,
+    };
+
+    let tokens = start_session(&store, account_id, AuthMethod::Oidc, Utc::now()).await?;
+    Ok(warp::reply::json(&tokens))
+}
+
+/// Link a new identity to the account with its email address, creating the account when
+/// there is none
+async fn link_identity(
+    store: &Store,
+    identity: &ExternalIdentity,
+) -> Result<AccountId, handle_errors::Error> {
+    let email = match &identity.email {
+        Some(email) if identity.email_verified => email.clone(),
+        _ => return Err(handle_errors::Error::EmailNotVerified),
+    };
+
+    let account_id = match store.get_account(email.clone()).await {
+        Ok(account) => {
+            let account_id = account.id.expect("id not found");
+            // Anyone can register an address without owning it, such an account must not
+            // be taken over by the owner of the address
+            if !store.is_email_verified(&account_id).await? {
+                return Err(handle_errors::Error::OidcAccountConflict);
+            }
+            store.add_identity(&account_id, identity).await?;
+            account_id
+        }
+        Err(handle_errors::Error::DatabaseQueryError(sqlx::Error::RowNotFound)) => {
+            store.add_identity_account(&email, identity).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    tracing::event!(
+        tracing::Level::INFO,
+        account_id = account_id.0,
+        issuer = %identity.issuer,
+        "Identity linked"
+    );
+    Ok(account_id)
+}
@@ -1,45 +1,146 @@
+use percent_encoding::percent_decode_str;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tracing::{instrument, Level};
+use warp::http::header::{HeaderValue, ETAG, LINK};
 use warp::http::StatusCode;
+use warp::Reply;
 
+use crate::content_filter::ContentModerator;
+use crate::purge::RetentionPolicy;
 use crate::store::Store;
-use crate::types::account::Session;
-use crate::types::pagination::{extract_pagniation, Pagination};
-use crate::types::question::{NewQuestion, Question};
+use crate::types::account::{Role, Session};
+use crate::types::moderation::ContentKind;
+use crate::types::pagination::{extract_pagination, link_header, Page, Pagination};
+use crate::types::question::{
+    extract_question_filter, AcceptAnswer, NewQuestion, Question, QuestionFilter,
+};
+use crate::types::version::{etag, IfMatch};
+use crate::types::vote::NewVote;
 
 #[instrument]
 pub async fn get_questions(
-    params: HashMap<String, String>,
+    params: Vec<(String, String)>,
+    session: Option<Session>,
     store: Store,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     tracing::event!(target: "warp_exp", Level::INFO, "Querying Question");
-    let mut pagination = Pagination::default();
+    let filter = extract_question_filter(&params)?;
+    let pagination = extract_pagination(&params.iter().cloned().collect())?;
 
-    if !params.is_empty() {
-        tracing::event!(Level::INFO, pagination = true);
-        pagination = extract_pagniation(params)?;
-    }
+    tracing::info!(pagination = ?pagination, filter = ?filter);
+    paginated_questions("/questions", &params, pagination, filter, session, store).await
+}
 
-    tracing::info!(pagination = ?pagination);
-    let res: Vec<Question> = match store
-        .get_questions(pagination.limit, pagination.offset)
-        .await
-    {
+#[instrument]
+pub async fn get_tag_questions(
+    tag: String,
+    params: Vec<(String, String)>,
+    session: Option<Session>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying Questions tagged {}", tag);
+    let pagination = extract_pagination(&params.iter().cloned().collect())?;
+    // The segment is still percent-encoded, which is what the links need
+    let path = format!("/tags/{}/questions", tag);
+    let tag = percent_decode_str(&tag)
+        .decode_utf8()
+        .map_err(|_| handle_errors::Error::InvalidParameter("tag".to_string()))?
+        .into_owned();
+    let filter = QuestionFilter {
+        tags: vec![tag],
+        ..QuestionFilter::default()
+    };
+
+    paginated_questions(&path, &params, pagination, filter, session, store).await
+}
+
+async fn paginated_questions(
+    path: &str,
+    params: &[(String, String)],
+    pagination: Pagination,
+    filter: QuestionFilter,
+    session: Option<Session>,
+    store: Store,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let mut res: Page<Question> = match store.get_questions(pagination.clone(), filter).await {
         Ok(res) => res,
         Err(e) => return Err(warp::reject::custom(e)),
     };
-    Ok(warp::reply::json(&res))
+    if let Some(session) = session {
+        store
+            .set_question_viewers(&mut res.items, &session.account_id)
+            .await?;
+    }
+
+    let link = link_header(path, params, &pagination, res.next_cursor.as_deref());
+    let mut response = warp::reply::json(&res).into_response();
+    if let Some(value) = link.and_then(|link| HeaderValue::from_str(&link).ok()) {
+        response.headers_mut().insert(LINK, value);
+    }
+    Ok(response)
+}
+
+#[instrument]
+pub async fn get_tags(store: Store) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying Tags");
+
+    match store.get_tags().await {
+        Ok(tags) => Ok(warp::reply::json(&tags)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+#[instrument]
+pub async fn get_question(
+    id: i32,
+    session: Option<Session>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    tracing::event!(target: "warp_exp", Level::INFO, "Querying Question {}", id);
+
+    let mut question = match store.get_question(id).await {
+        Ok(question) => question,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    if let Some(session) = session {
+        store
+            .set_question_viewers(
+                std::slice::from_mut(&mut question.question),
+                &session.account_id,
+            )
+            .await?;
+        store
+            .set_answer_viewers(&mut question.answers, &session.account_id)
+            .await?;
+    }
+    let version = question.question.version;
+    Ok(warp::reply::with_header(
+        warp::reply::json(&question),
+        ETAG,
+        etag(version),
+    ))
 }
 
 pub async fn add_question(
     store: Store,
+    moderator: Arc<ContentModerator>,
     session: Session,
     new_question: NewQuestion,
 ) -> Result<impl warp::Reply, warp::Rejection> {
     let account_id = session.account_id;
+    let new_question = NewQuestion {
+        title: moderator.moderate("title", new_question.title).await?,
+        content: moderator.moderate("content", new_question.content).await?,
+        tags: new_question.tags,
+    };
 
     match store.add_question(new_question, account_id).await {
-        Ok(question) => Ok(warp::reply::json(&question)),
+        Ok(question) => Ok(warp::reply::with_header(
+            warp::reply::json(&question),
+            ETAG,
+            etag(question.version),
+        )),
         Err(e) => Err(warp::reject::custom(e)),
     }
 }
@@ -48,16 +149,32 @@ pub async fn update_question(
     id: i32,
     session: Session,
     store: Store,
+    moderator: Arc<ContentModerator>,
+    if_match: Option<String>,
     question: Question,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let account_id = session.account_id;
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
 
-    if store.is_question_owner(id, &account_id).await? {
-        let res = match store.update_question(question, id).await {
+    // Moderators can change any question, not only their own
+    if session.has_role(Role::Moderator) || store.is_question_owner(id, &account_id).await? {
+        let question = Question {
+            title: moderator.moderate("title", question.title).await?,
+            content: moderator.moderate("content", question.content).await?,
+            ..question
+        };
+        let res = match store
+            .update_question(question, id, &account_id, &if_match)
+            .await
+        {
             Ok(res) => res,
             Err(e) => return Err(warp::reject::custom(e)),
         };
-        Ok(warp::reply::json(&res))
+        Ok(warp::reply::with_header(
+            warp::reply::json(&res),
+            ETAG,
+            etag(res.version),
+        ))
     } else {
         Err(warp::reject::custom(handle_errors::Error::Unauthorized))
     }
@@ -67,11 +184,16 @@ pub async fn delete_question(
     id: i32,
     session: Session,
     store: Store,
+    if_match: Option<String>,
 ) -> Result<impl warp::Reply, warp::Rejection> {
-    let account_id = session.account_id;
-    if store.is_question_owner(id, &account_id).await? {
-        if let Err(e) = store.delete_question(id).await {
-            return Err(warp::reject::custom(e));
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
+    // Moderators can change any question, not only their own
+    if session.has_role(Role::Moderator) || store.is_question_owner(id, &account_id).await? {
+        match store.delete_question(id, &account_id, &if_match).await {
+            Ok(true) => {}
+            Ok(false) => return Err(warp::reject::custom(handle_errors::Error::QuestionNotFound)),
+            Err(e) => return Err(warp::reject::custom(e)),
         };
 
         Ok(warp::reply::with_status(
@@ -82,3 +204,165 @@ pub async fn delete_question(
         Err(warp::reject::custom(handle_errors::Error::Unauthorized))
     }
 }
+
+pub async fn accept_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+    accept: AcceptAnswer,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+
+    if store.is_question_owner(id, &account_id).await? {
+        match store.accept_answer(id, Some(accept.answer_id)).await {
+            Ok(question) => Ok(warp::reply::json(&question)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+pub async fn unaccept_answer(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let account_id = session.account_id;
+
+    if store.is_question_owner(id, &account_id).await? {
+        match store.accept_answer(id, None).await {
+            Ok(question) => Ok(warp::reply::json(&question)),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/// Every revision of the question with a diff from the one before it
+pub async fn get_question_revisions(
+    id: i32,
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let pagination = extract_pagination(&params)?;
+
+    match store
+        .get_question_revisions(id, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(revisions) => Ok(warp::reply::json(&revisions)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+/// Restore the question to an earlier revision, recorded as a new revision
+pub async fn rollback_question(
+    id: i32,
+    revision: i32,
+    session: Session,
+    store: Store,
+    if_match: Option<String>,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let if_match = IfMatch::from_header(if_match);
+    let account_id = session.account_id.clone();
+
+    // Moderators can change any question, not only their own
+    if session.has_role(Role::Moderator) || store.is_question_owner(id, &account_id).await? {
+        match store
+            .rollback_question(id, revision, &account_id, &if_match)
+            .await
+        {
+            Ok(question) => Ok(warp::reply::with_header(
+                warp::reply::json(&question),
+                ETAG,
+                etag(question.version),
+            )),
+            Err(e) => Err(warp::reject::custom(e)),
+        }
+    } else {
+        Err(warp::reject::custom(handle_errors::Error::Unauthorized))
+    }
+}
+
+/// Bring back a soft deleted question, see `RetentionPolicy::check_undelete` for who may
+pub async fn undelete_question(
+    id: i32,
+    session: Session,
+    store: Store,
+    policy: RetentionPolicy,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let deletion = store
+        .get_deletion(ContentKind::Question, id)
+        .await?
+        .ok_or(handle_errors::Error::NotDeleted)?;
+    policy.check_undelete(&deletion, &session)?;
+
+    if !store.undelete(ContentKind::Question, id).await? {
+        // Undeleted by someone else in the meantime
+        return Err(warp::reject::custom(handle_errors::Error::NotDeleted));
+    }
+
+    Ok(warp::reply::with_status(
+        format!("Question {} Undeleted", id),
+        StatusCode::OK,
+    ))
+}
+
+pub async fn vote_question(
+    id: i32,
+    session: Session,
+    store: Store,
+    vote: NewVote,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store
+        .vote_question(id, session.account_id, Some(vote.vote))
+        .await
+    {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn retract_question_vote(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.vote_question(id, session.account_id, None).await {
+        Ok(score) => Ok(warp::reply::json(&score)),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn bookmark_question(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store.bookmark_question(id, &session.account_id, true).await {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Question {} Bookmarked", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
+
+pub async fn remove_bookmark(
+    id: i32,
+    session: Session,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    match store
+        .bookmark_question(id, &session.account_id, false)
+        .await
+    {
+        Ok(_) => Ok(warp::reply::with_status(
+            format!("Bookmark of Question {} Removed", id),
+            StatusCode::OK,
+        )),
+        Err(e) => Err(warp::reject::custom(e)),
+    }
+}
@@ -0,0 +1,25 @@
+use std::collections::HashMap;
+use tracing::{instrument, Level};
+
+use crate::store::Store;
+use crate::types::pagination::extract_pagination;
+use crate::types::search::{extract_search_query, SearchResult};
+
+#[instrument]
+pub async fn search(
+    params: HashMap<String, String>,
+    store: Store,
+) -> Result<impl warp::Reply, warp::Rejection> {
+    let query = extract_search_query(&params)?;
+    tracing::event!(target: "warp_exp", Level::INFO, "Searching for {}", query);
+    let pagination = extract_pagination(&params)?;
+
+    let res: Vec<SearchResult> = match store
+        .search(&query, pagination.limit, pagination.offset)
+        .await
+    {
+        Ok(res) => res,
+        Err(e) => return Err(warp::reject::custom(e)),
+    };
+    Ok(warp::reply::json(&res))
+}
@@ -1,11 +1,52 @@
-use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
-use sqlx::Row;
+use sqlx::postgres::{PgPool, PgPoolOptions, PgRow, Postgres};
+use sqlx::{Row, Transaction};
 
-use crate::types::account::{Account, AccountId};
-use crate::types::answer::{Answer, AnswerId, NewAnswer};
-use crate::types::question::{NewQuestion, Question, QuestionId};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+use crate::types::account::{
+    Account, AccountExport, AccountId, AccountSummary, AuthMethod, BookmarkRecord, DeletionPolicy,
+    EmailVerification, LoginAttemptKind, PasswordReset, Profile, ProfileUpdate, PublicProfile,
+    Role, Session, SessionRecord, StoredSession, TokenRecord, VoteRecord,
+};
+use crate::types::answer::{Answer, AnswerId, AnswerViewer, NewAnswer};
+use crate::types::api_key::{ApiKey, ApiKeyScope, ApiKeySummary, NewApiKey};
+use crate::types::deletion::Deletion;
+use crate::types::moderation::{
+    ContentKind, Flag, FlagThreshold, ModerationAction, ModerationRecord, ModerationState,
+    QueueItem,
+};
+use crate::types::oidc::{ExternalIdentity, IdentityRecord, PendingOidcLogin};
+use crate::types::pagination::{Cursor, CursorKey, Page, Pagination};
+use crate::types::question::{
+    NewQuestion, Question, QuestionFilter, QuestionId, QuestionSort, QuestionViewer,
+    QuestionWithAnswers,
+};
+use crate::types::revision::{question_text, unified_diff, AnswerRevision, QuestionRevision};
+use crate::types::search::{SearchResult, SearchResultKind};
+use crate::types::tag::Tag;
+use crate::types::version::IfMatch;
+use crate::types::vote::{VoteScore, VoteValue};
 use handle_errors::Error;
 
+/// Save the current state of a question as its next revision, binds the question id and
+/// the editor
+const ADD_QUESTION_REVISION: &str = "INSERT INTO question_revisions
+    (question_id, revision, title, content, tags, editor_id)
+    SELECT id, COALESCE(
+        (SELECT MAX(revision) FROM question_revisions WHERE question_id = questions.id), 0
+    ) + 1, title, content, tags, $2
+    FROM questions WHERE id = $1";
+
+/// Save the current state of an answer as its next revision, binds the answer id and the
+/// editor
+const ADD_ANSWER_REVISION: &str = "INSERT INTO answer_revisions
+    (answer_id, revision, content, editor_id)
+    SELECT id, COALESCE(
+        (SELECT MAX(revision) FROM answer_revisions WHERE answer_id = answers.id), 0
+    ) + 1, content, $2
+    FROM answers WHERE id = $1";
+
 #[derive(Clone, Debug)]
 pub struct Store {
     pub connection: PgPool,
@@ -29,11 +70,13 @@ impl Store {
     }
 
     pub async fn is_question_owner(&self, id: i32, account_id: &AccountId) -> Result<bool, Error> {
-        match sqlx::query("SELECT * FROM questions WHERE id = $1 AND account_id = $2")
-            .bind(id)
-            .bind(account_id.0)
-            .fetch_optional(&self.connection)
-            .await
+        match sqlx::query(
+            "SELECT * FROM questions WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .fetch_optional(&self.connection)
+        .await
         {
             Ok(question) => Ok(question.is_some()),
             Err(e) => {
@@ -43,24 +86,248 @@ impl Store {
         }
     }
 
+    pub async fn is_answer_owner(&self, id: i32, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query(
+            "SELECT * FROM answers WHERE id = $1 AND account_id = $2 AND deleted_at IS NULL",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(answer) => Ok(answer.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
     pub async fn get_questions(
         &self,
-        limit: Option<u32>,
-        offset: u32,
-    ) -> Result<Vec<Question>, Error> {
-        match sqlx::query("SELECT * FROM questions LIMIT $1 OFFSET $2")
-            .bind(limit)
-            .bind(offset)
-            .map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
+        pagination: Pagination,
+        filter: QuestionFilter,
+    ) -> Result<Page<Question>, Error> {
+        let filters = "moderation_state = 'visible' AND deleted_at IS NULL
+            AND ($1::boolean IS NULL OR (accepted_answer_id IS NOT NULL) = $1)
+            AND ($2::text[] IS NULL OR (CASE WHEN $3 THEN tags @> $2 ELSE tags && $2 END))";
+        let (order_by, keyset) = match filter.sort {
+            None => ("id", "id > $6"),
+            Some(QuestionSort::Score) => ("score DESC, id DESC", "(score, id) < ($7, $6)"),
+            Some(QuestionSort::Newest) => {
+                ("created_on DESC, id DESC", "(created_on, id) < ($7, $6)")
+            }
+            Some(QuestionSort::Activity) => {
+                ("activity_on DESC, id DESC", "(activity_on, id) < ($7, $6)")
+            }
+        };
+        let query = format!(
+            "SELECT * FROM (
+                SELECT *, GREATEST(created_on, (SELECT MAX(a.created_on) FROM answers a
+                    WHERE a.question_id = questions.id AND a.moderation_state = 'visible'
+                        AND a.deleted_at IS NULL))
+                    AS activity_on
+                FROM questions
+            ) questions
+            WHERE {} AND ($6::integer IS NULL OR {})
+            ORDER BY {} LIMIT $4 OFFSET $5",
+            filters, keyset, order_by
+        );
+        let tags = if filter.tags.is_empty() {
+            None
+        } else {
+            Some(filter.tags)
+        };
+
+        let after = match pagination.after {
+            Some(cursor) => {
+                let matches_sort = matches!(
+                    (&cursor.key, filter.sort),
+                    (CursorKey::Id, None)
+                        | (CursorKey::Score(_), Some(QuestionSort::Score))
+                        | (CursorKey::CreatedOn(_), Some(QuestionSort::Newest))
+                        | (CursorKey::Activity(_), Some(QuestionSort::Activity))
+                );
+                if !matches_sort {
+                    return Err(Error::InvalidParameter("after".to_string()));
+                }
+                Some(cursor)
+            }
+            None => None,
+        };
+
+        let total = match sqlx::query(&format!(
+            "SELECT COUNT(*) AS total FROM questions WHERE {}",
+            filters
+        ))
+        .bind(filter.answered)
+        .bind(&tags)
+        .bind(filter.match_all_tags)
+        .map(|row: PgRow| row.get::<i64, _>("total"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(total) => total,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        // Fetch one more row than asked for to know whether there is a next page
+        let query = sqlx::query(&query)
+            .bind(filter.answered)
+            .bind(&tags)
+            .bind(filter.match_all_tags)
+            .bind(pagination.limit.map(|limit| i64::from(limit) + 1))
+            .bind(i64::from(pagination.offset))
+            .bind(after.as_ref().map(|cursor| cursor.id));
+        // The sort key is bound even without a cursor so its type matches the sort column
+        let key = after.map(|cursor| cursor.key);
+        let query = match filter.sort {
+            None => query.bind(None::<i32>),
+            Some(QuestionSort::Score) => query.bind(match key {
+                Some(CursorKey::Score(score)) => Some(score),
+                _ => None,
+            }),
+            Some(QuestionSort::Newest) | Some(QuestionSort::Activity) => query.bind(match key {
+                Some(CursorKey::CreatedOn(at)) | Some(CursorKey::Activity(at)) => Some(at),
+                _ => None,
+            }),
+        };
+
+        let sort = filter.sort;
+        match query
+            .map(|row: PgRow| {
+                let key = match sort {
+                    None => CursorKey::Id,
+                    Some(QuestionSort::Score) => CursorKey::Score(row.get("score")),
+                    Some(QuestionSort::Newest) => CursorKey::CreatedOn(row.get("created_on")),
+                    Some(QuestionSort::Activity) => CursorKey::Activity(row.get("activity_on")),
+                };
+                let question = Question {
+                    id: QuestionId(row.get("id")),
+                    title: row.get("title"),
+                    content: row.get("content"),
+                    tags: row.get("tags"),
+                    accepted_answer_id: row
+                        .get::<Option<i32>, _>("accepted_answer_id")
+                        .map(AnswerId),
+                    score: row.get("score"),
+                    version: row.get("version"),
+                    viewer: None,
+                };
+                (question, key)
             })
             .fetch_all(&self.connection)
             .await
         {
-            Ok(question) => Ok(question),
+            Ok(mut rows) => {
+                let mut next_cursor = None;
+                if let Some(limit) = pagination.limit {
+                    if rows.len() > limit as usize {
+                        rows.truncate(limit as usize);
+                        next_cursor = rows.last().map(|(question, key)| {
+                            Cursor {
+                                id: question.id.0,
+                                key: key.clone(),
+                            }
+                            .encode()
+                        });
+                    }
+                }
+
+                Ok(Page {
+                    items: rows.into_iter().map(|(question, _)| question).collect(),
+                    total,
+                    next_cursor,
+                })
+            }
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_tags(&self) -> Result<Vec<Tag>, Error> {
+        match sqlx::query(
+            "SELECT tag, COUNT(DISTINCT id) AS question_count
+            FROM questions, unnest(tags) AS tag
+            WHERE moderation_state = 'visible' AND deleted_at IS NULL
+            GROUP BY tag ORDER BY question_count DESC, tag",
+        )
+        .map(|row: PgRow| Tag {
+            name: row.get("tag"),
+            question_count: row.get("question_count"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(tags) => Ok(tags),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_question(&self, question_id: i32) -> Result<QuestionWithAnswers, Error> {
+        let question = match sqlx::query(
+            "SELECT * FROM questions
+            WHERE id = $1 AND moderation_state = 'visible' AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| {
+            (
+                Question {
+                    id: QuestionId(row.get("id")),
+                    title: row.get("title"),
+                    content: row.get("content"),
+                    tags: row.get("tags"),
+                    accepted_answer_id: row
+                        .get::<Option<i32>, _>("accepted_answer_id")
+                        .map(AnswerId),
+                    score: row.get("score"),
+                    version: row.get("version"),
+                    viewer: None,
+                },
+                row.get::<Option<i32>, _>("account_id").map(AccountId),
+            )
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(question)) => question,
+            Ok(None) => return Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        match sqlx::query(
+            "SELECT * FROM answers
+            WHERE question_id = $1 AND moderation_state = 'visible' AND deleted_at IS NULL
+            ORDER BY id",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(answers) => Ok(QuestionWithAnswers {
+                question: question.0,
+                account_id: question.1,
+                answers,
+            }),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -73,10 +340,16 @@ impl Store {
         new_question: NewQuestion,
         account_id: AccountId,
     ) -> Result<Question, Error> {
-        match sqlx::query(
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let question = match sqlx::query(
             "INSERT INTO questions (title, content, tags, account_id)
             VALUES ($1, $2, $3, $4)
-            RETURNING id, title, content, tags",
+            RETURNING id, title, content, tags, accepted_answer_id, score, version",
         )
         .bind(new_question.title)
         .bind(new_question.content)
@@ -87,65 +360,231 @@ impl Store {
             title: row.get("title"),
             content: row.get("content"),
             tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
         })
-        .fetch_one(&self.connection)
+        .fetch_one(&mut tx)
         .await
         {
-            Ok(question) => Ok(question),
+            Ok(question) => question,
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
+                return Err(Error::DatabaseQueryError(e));
             }
+        };
+
+        if let Err(e) = sqlx::query(ADD_QUESTION_REVISION)
+            .bind(question.id.0)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
         }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(question)
     }
 
+    /// Save the edit of `editor_id` as a new revision of the question, as long as the
+    /// question is still in a version `if_match` accepts
     pub async fn update_question(
         &self,
         question: Question,
         question_id: i32,
+        editor_id: &AccountId,
+        if_match: &IfMatch,
     ) -> Result<Question, Error> {
-        match sqlx::query(
-            "UPDATE questions SET title=$1, content=$2, tags=$3 WHERE id = $4 RETURNING id, title, content,tags"
-        ).bind(question.title).bind(question.content).bind(question.tags).bind(question_id).map(|row: PgRow| Question {
-                id: QuestionId(row.get("id")),
-                title: row.get("title"),
-                content: row.get("content"),
-                tags: row.get("tags"),
-            })
-                .fetch_one(&self.connection)
-            .await {
-            Ok(question) => Ok(question),
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Question, question_id, if_match).await?;
+
+        let question = match sqlx::query(
+            "UPDATE questions SET title = $1, content = $2, tags = $3, version = version + 1
+            WHERE id = $4 AND deleted_at IS NULL
+            RETURNING id, title, content, tags, accepted_answer_id, score, version",
+        )
+        .bind(question.title)
+        .bind(question.content)
+        .bind(question.tags)
+        .bind(question_id)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(question)) => question,
+            Ok(None) => return Err(Error::QuestionNotFound),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
+                return Err(Error::DatabaseQueryError(e));
             }
+        };
+
+        if let Err(e) = sqlx::query(ADD_QUESTION_REVISION)
+            .bind(question_id)
+            .bind(editor_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
         }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(question)
     }
 
-    pub async fn delete_question(&self, question_id: i32) -> Result<bool, Error> {
-        match sqlx::query("DELETE FROM questions WHERE id = $1")
-            .bind(question_id)
-            .execute(&self.connection)
+    /// Restore the question to `revision`, saved as a new revision by `editor_id`
+    pub async fn rollback_question(
+        &self,
+        question_id: i32,
+        revision: i32,
+        editor_id: &AccountId,
+        if_match: &IfMatch,
+    ) -> Result<Question, Error> {
+        let mut tx = self
+            .connection
+            .begin()
             .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Question, question_id, if_match).await?;
+
+        let question = match sqlx::query(
+            "UPDATE questions q
+            SET title = r.title, content = r.content, tags = r.tags, version = q.version + 1
+            FROM question_revisions r
+            WHERE q.id = $1 AND q.deleted_at IS NULL
+                AND r.question_id = q.id AND r.revision = $2
+            RETURNING q.id, q.title, q.content, q.tags, q.accepted_answer_id, q.score, q.version",
+        )
+        .bind(question_id)
+        .bind(revision)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_optional(&mut tx)
+        .await
         {
-            Ok(_) => Ok(true),
+            Ok(Some(question)) => question,
+            Ok(None) => return Err(Error::RevisionNotFound),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
-                Err(Error::DatabaseQueryError(e))
+                return Err(Error::DatabaseQueryError(e));
             }
+        };
+
+        if let Err(e) = sqlx::query(ADD_QUESTION_REVISION)
+            .bind(question_id)
+            .bind(editor_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
         }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(question)
     }
 
-    pub async fn add_answer(
+    /// Revisions of a visible question, oldest first
+    pub async fn get_question_revisions(
         &self,
-        new_answer: NewAnswer,
-        account_id: AccountId,
-    ) -> Result<Answer, Error> {
-        match sqlx::query("INSERT INTO answers (content, question_id, account_id) VALUES ($1, $2, $3) RETURNING id, question_id, content").bind(new_answer.content).bind(new_answer.question_id.0).bind(account_id.0).map(|row: PgRow| Answer {
-            id: AnswerId(row.get("id")),
-            content: row.get("content"),
-            question_id: QuestionId(row.get("question_id"))}).fetch_one(&self.connection).await{
-            Ok(answer) => Ok(answer),
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<QuestionRevision>, Error> {
+        match sqlx::query(
+            "SELECT id FROM questions
+            WHERE id = $1 AND moderation_state = 'visible' AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        // The previous revision is looked up before paginating, so the first one of a page
+        // still gets its diff
+        match sqlx::query(
+            "SELECT *, LAG(title) OVER w AS previous_title,
+                LAG(content) OVER w AS previous_content, LAG(tags) OVER w AS previous_tags
+            FROM question_revisions WHERE question_id = $1
+            WINDOW w AS (ORDER BY revision)
+            ORDER BY revision LIMIT $2 OFFSET $3",
+        )
+        .bind(question_id)
+        .bind(limit.map(i64::from))
+        .bind(i64::from(offset))
+        .map(|row: PgRow| {
+            let revision: i32 = row.get("revision");
+            let title: String = row.get("title");
+            let content: String = row.get("content");
+            let tags: Option<Vec<String>> = row.get("tags");
+            let previous = row
+                .get::<Option<String>, _>("previous_title")
+                .map(|previous_title| {
+                    question_text(
+                        &previous_title,
+                        row.get("previous_content"),
+                        row.get::<Option<Vec<String>>, _>("previous_tags")
+                            .as_deref(),
+                    )
+                });
+            let diff = unified_diff(
+                previous.as_deref(),
+                &question_text(&title, &content, tags.as_deref()),
+                revision,
+            );
+
+            QuestionRevision {
+                revision,
+                editor_id: row.get::<Option<i32>, _>("editor_id").map(AccountId),
+                created_on: row.get("created_on"),
+                title,
+                content,
+                tags,
+                diff,
+            }
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(revisions) => Ok(revisions),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
@@ -153,70 +592,2397 @@ impl Store {
         }
     }
 
-    pub async fn add_account(self, account: Account) -> Result<bool, Error> {
-        match sqlx::query("INSERT INTO accounts (email, password) VALUES ($1, $2)")
-            .bind(account.email)
-            .bind(account.password)
-            .execute(&self.connection)
+    /// Soft delete the question, its answers are hidden along with it. Returns `false` when
+    /// there is no such question left to delete.
+    pub async fn delete_question(
+        &self,
+        question_id: i32,
+        deleted_by: &AccountId,
+        if_match: &IfMatch,
+    ) -> Result<bool, Error> {
+        let mut tx = self
+            .connection
+            .begin()
             .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Question, question_id, if_match).await?;
+
+        let deleted = match sqlx::query(
+            "UPDATE questions SET deleted_at = NOW(), deleted_by = $2
+            WHERE id = $1 AND deleted_at IS NULL",
+        )
+        .bind(question_id)
+        .bind(deleted_by.0)
+        .execute(&mut tx)
+        .await
         {
-            Ok(_) => Ok(true),
-            Err(error) => {
-                tracing::event!(
-                    tracing::Level::ERROR,
-                    code = error
-                        .as_database_error()
-                        .unwrap()
-                        .code()
-                        .unwrap()
-                        .parse::<i32>()
-                        .unwrap(),
-                    db_message = error.as_database_error().unwrap().message(),
-                    constraint = error.as_database_error().unwrap().constraint().unwrap(),
-                );
-                Err(Error::DatabaseQueryError(error))
+            Ok(res) => res.rows_affected() > 0,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
             }
-        }
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(deleted)
     }
 
-    pub async fn get_account(&self, email: String) -> Result<Account, Error> {
-        match sqlx::query("SELECT * from accounts where email = $1")
-            .bind(email)
-            .map(|row: PgRow| Account {
-                id: Some(AccountId(row.get("id"))),
-                email: row.get("email"),
-                password: row.get("password"),
-            })
-            .fetch_one(&self.connection)
+    pub async fn add_answer(
+        &self,
+        new_answer: NewAnswer,
+        account_id: AccountId,
+    ) -> Result<Answer, Error> {
+        let mut tx = self
+            .connection
+            .begin()
             .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        // Hold the question so it cannot be deleted or hidden while the answer goes in
+        match sqlx::query(
+            "SELECT id FROM questions
+            WHERE id = $1 AND deleted_at IS NULL AND moderation_state = 'visible' FOR SHARE",
+        )
+        .bind(new_answer.question_id.0)
+        .fetch_optional(&mut tx)
+        .await
         {
-            Ok(account) => {
-                println!("Got account!");
-                Ok(account)
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
             }
-            Err(error) => {
-                tracing::event!(tracing::Level::ERROR, "{:?}", error);
-                Err(Error::DatabaseQueryError(error))
+        }
+
+        let answer = match sqlx::query(
+            "INSERT INTO answers (content, question_id, account_id) VALUES ($1, $2, $3)
+            RETURNING id, question_id, content, score, version",
+        )
+        .bind(new_answer.content)
+        .bind(new_answer.question_id.0)
+        .bind(account_id.0)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(answer) => answer,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
             }
+        };
+
+        if let Err(e) = sqlx::query(ADD_ANSWER_REVISION)
+            .bind(answer.id.0)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
         }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(answer)
     }
 
-    pub async fn reset_password(
+    pub async fn get_answers(
         &self,
-        account: Account,
-        hashed_password: String,
-    ) -> Result<(), Error> {
-        match sqlx::query("UPDATE accounts SET password = $1 WHERE email = $2")
-            .bind(hashed_password)
-            .bind(account.email)
-            .fetch_optional(&self.connection)
-            .await
+        question_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<Answer>, Error> {
+        match sqlx::query(
+            "SELECT a.* FROM answers a JOIN questions q ON q.id = a.question_id
+            WHERE a.question_id = $1 AND a.moderation_state = 'visible' AND a.deleted_at IS NULL
+                AND q.moderation_state = 'visible' AND q.deleted_at IS NULL
+            ORDER BY a.id LIMIT $2 OFFSET $3",
+        )
+        .bind(question_id)
+        .bind(limit.map(i64::from))
+        .bind(i64::from(offset))
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_all(&self.connection)
+        .await
         {
-            Ok(_) => Ok(()),
+            Ok(answers) => Ok(answers),
             Err(e) => {
                 tracing::event!(tracing::Level::ERROR, "{:?}", e);
                 Err(Error::DatabaseQueryError(e))
             }
         }
     }
+
+    /// Save the edit of `editor_id` as a new revision of the answer, as long as the answer
+    /// is still in a version `if_match` accepts
+    pub async fn update_answer(
+        &self,
+        answer: Answer,
+        answer_id: i32,
+        editor_id: &AccountId,
+        if_match: &IfMatch,
+    ) -> Result<Answer, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Answer, answer_id, if_match).await?;
+
+        let answer = match sqlx::query(
+            "UPDATE answers SET content = $1, version = version + 1
+            WHERE id = $2 AND deleted_at IS NULL
+            RETURNING id, question_id, content, score, version",
+        )
+        .bind(answer.content)
+        .bind(answer_id)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(answer)) => answer,
+            Ok(None) => return Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        if let Err(e) = sqlx::query(ADD_ANSWER_REVISION)
+            .bind(answer_id)
+            .bind(editor_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(answer)
+    }
+
+    /// Restore the answer to `revision`, saved as a new revision by `editor_id`
+    pub async fn rollback_answer(
+        &self,
+        answer_id: i32,
+        revision: i32,
+        editor_id: &AccountId,
+        if_match: &IfMatch,
+    ) -> Result<Answer, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Answer, answer_id, if_match).await?;
+
+        let answer = match sqlx::query(
+            "UPDATE answers a SET content = r.content, version = a.version + 1
+            FROM answer_revisions r
+            WHERE a.id = $1 AND a.deleted_at IS NULL
+                AND r.answer_id = a.id AND r.revision = $2
+            RETURNING a.id, a.question_id, a.content, a.score, a.version",
+        )
+        .bind(answer_id)
+        .bind(revision)
+        .map(|row: PgRow| Answer {
+            id: AnswerId(row.get("id")),
+            content: row.get("content"),
+            question_id: QuestionId(row.get("question_id")),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(answer)) => answer,
+            Ok(None) => return Err(Error::RevisionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        if let Err(e) = sqlx::query(ADD_ANSWER_REVISION)
+            .bind(answer_id)
+            .bind(editor_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(answer)
+    }
+
+    /// Revisions of a visible answer, oldest first
+    pub async fn get_answer_revisions(
+        &self,
+        answer_id: i32,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<AnswerRevision>, Error> {
+        match sqlx::query(
+            "SELECT a.id FROM answers a JOIN questions q ON q.id = a.question_id
+            WHERE a.id = $1 AND a.moderation_state = 'visible' AND a.deleted_at IS NULL
+                AND q.moderation_state = 'visible' AND q.deleted_at IS NULL",
+        )
+        .bind(answer_id)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        match sqlx::query(
+            "SELECT *, LAG(content) OVER (ORDER BY revision) AS previous_content
+            FROM answer_revisions WHERE answer_id = $1
+            ORDER BY revision LIMIT $2 OFFSET $3",
+        )
+        .bind(answer_id)
+        .bind(limit.map(i64::from))
+        .bind(i64::from(offset))
+        .map(|row: PgRow| {
+            let revision: i32 = row.get("revision");
+            let content: String = row.get("content");
+            let diff = unified_diff(row.get("previous_content"), &content, revision);
+
+            AnswerRevision {
+                revision,
+                editor_id: row.get::<Option<i32>, _>("editor_id").map(AccountId),
+                created_on: row.get("created_on"),
+                content,
+                diff,
+            }
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(revisions) => Ok(revisions),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Soft delete the answer, returns `false` when there is no such answer left to delete.
+    ///
+    /// Like a hard delete would, this takes back the acceptance of the answer, undeleting it
+    /// doesn't accept it again.
+    pub async fn delete_answer(
+        &self,
+        answer_id: i32,
+        deleted_by: &AccountId,
+        if_match: &IfMatch,
+    ) -> Result<bool, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+        lock_version(&mut tx, ContentKind::Answer, answer_id, if_match).await?;
+
+        let deleted = match sqlx::query(
+            "WITH deleted AS (
+                UPDATE answers SET deleted_at = NOW(), deleted_by = $2
+                WHERE id = $1 AND deleted_at IS NULL
+                RETURNING id
+            ), unaccepted AS (
+                UPDATE questions SET accepted_answer_id = NULL
+                WHERE accepted_answer_id IN (SELECT id FROM deleted)
+            )
+            SELECT COUNT(*) AS deleted FROM deleted",
+        )
+        .bind(answer_id)
+        .bind(deleted_by.0)
+        .map(|row: PgRow| row.get::<i64, _>("deleted"))
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(deleted) => deleted > 0,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(deleted)
+    }
+
+    pub async fn accept_answer(
+        &self,
+        question_id: i32,
+        answer_id: Option<AnswerId>,
+    ) -> Result<Question, Error> {
+        if let Some(answer_id) = &answer_id {
+            match sqlx::query(
+                "SELECT question_id FROM answers
+                WHERE id = $1 AND deleted_at IS NULL AND moderation_state = 'visible'",
+            )
+            .bind(answer_id.0)
+            .map(|row: PgRow| row.get::<i32, _>("question_id"))
+            .fetch_optional(&self.connection)
+            .await
+            {
+                Ok(Some(answer_question_id)) if answer_question_id == question_id => {}
+                Ok(Some(_)) => return Err(Error::AnswerNotInQuestion),
+                Ok(None) => return Err(Error::AnswerNotFound),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            }
+        }
+
+        match sqlx::query(
+            "UPDATE questions SET accepted_answer_id = $1
+            WHERE id = $2 AND deleted_at IS NULL AND moderation_state = 'visible'
+            RETURNING id, title, content, tags, accepted_answer_id, score, version",
+        )
+        .bind(answer_id.map(|id| id.0))
+        .bind(question_id)
+        .map(|row: PgRow| Question {
+            id: QuestionId(row.get("id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            tags: row.get("tags"),
+            accepted_answer_id: row
+                .get::<Option<i32>, _>("accepted_answer_id")
+                .map(AnswerId),
+            score: row.get("score"),
+            version: row.get("version"),
+            viewer: None,
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(question)) => Ok(question),
+            Ok(None) => Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn vote_question(
+        &self,
+        question_id: i32,
+        account_id: AccountId,
+        vote: Option<VoteValue>,
+    ) -> Result<VoteScore, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        // Lock the question so concurrent votes recompute the score one after another
+        match sqlx::query(
+            "SELECT id FROM questions WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        )
+        .bind(question_id)
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        let res = match vote {
+            Some(value) => sqlx::query(
+                "INSERT INTO question_votes (question_id, account_id, value) VALUES ($1, $2, $3)
+                ON CONFLICT (question_id, account_id) DO UPDATE SET value = EXCLUDED.value",
+            )
+            .bind(question_id)
+            .bind(account_id.0)
+            .bind(value.as_i16()),
+            None => {
+                sqlx::query("DELETE FROM question_votes WHERE question_id = $1 AND account_id = $2")
+                    .bind(question_id)
+                    .bind(account_id.0)
+            }
+        }
+        .execute(&mut tx)
+        .await;
+        if let Err(e) = res {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        let score = match sqlx::query(
+            "UPDATE questions SET score = (SELECT COALESCE(SUM(value), 0) FROM question_votes
+            WHERE question_id = $1) WHERE id = $1 RETURNING score",
+        )
+        .bind(question_id)
+        .map(|row: PgRow| row.get("score"))
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(score) => score,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+
+        Ok(VoteScore { score, vote })
+    }
+
+    pub async fn vote_answer(
+        &self,
+        answer_id: i32,
+        account_id: AccountId,
+        vote: Option<VoteValue>,
+    ) -> Result<VoteScore, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        // Lock the answer so concurrent votes recompute the score one after another
+        match sqlx::query("SELECT id FROM answers WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(answer_id)
+            .fetch_optional(&mut tx)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::AnswerNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        let res = match vote {
+            Some(value) => sqlx::query(
+                "INSERT INTO answer_votes (answer_id, account_id, value) VALUES ($1, $2, $3)
+                ON CONFLICT (answer_id, account_id) DO UPDATE SET value = EXCLUDED.value",
+            )
+            .bind(answer_id)
+            .bind(account_id.0)
+            .bind(value.as_i16()),
+            None => {
+                sqlx::query("DELETE FROM answer_votes WHERE answer_id = $1 AND account_id = $2")
+                    .bind(answer_id)
+                    .bind(account_id.0)
+            }
+        }
+        .execute(&mut tx)
+        .await;
+        if let Err(e) = res {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        let score = match sqlx::query(
+            "UPDATE answers SET score = (SELECT COALESCE(SUM(value), 0) FROM answer_votes
+            WHERE answer_id = $1) WHERE id = $1 RETURNING score",
+        )
+        .bind(answer_id)
+        .map(|row: PgRow| row.get("score"))
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(score) => score,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+
+        Ok(VoteScore { score, vote })
+    }
+
+    /// Fill in how the account relates to each of the questions
+    pub async fn set_question_viewers(
+        &self,
+        questions: &mut [Question],
+        account_id: &AccountId,
+    ) -> Result<(), Error> {
+        let ids: Vec<i32> = questions.iter().map(|question| question.id.0).collect();
+
+        let viewers: HashMap<i32, QuestionViewer> = match sqlx::query(
+            "SELECT q.id, v.value AS vote, q.account_id IS NOT DISTINCT FROM $2 AS is_author,
+                b.question_id IS NOT NULL AS bookmarked
+            FROM questions q
+            LEFT JOIN question_votes v ON v.question_id = q.id AND v.account_id = $2
+            LEFT JOIN question_bookmarks b ON b.question_id = q.id AND b.account_id = $2
+            WHERE q.id = ANY($1)",
+        )
+        .bind(ids)
+        .bind(account_id.0)
+        .map(|row: PgRow| {
+            (
+                row.get("id"),
+                QuestionViewer {
+                    vote: row.get::<Option<i16>, _>("vote").map(VoteValue::from_db),
+                    is_author: row.get("is_author"),
+                    bookmarked: row.get("bookmarked"),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(viewers) => viewers.into_iter().collect(),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        for question in questions.iter_mut() {
+            question.viewer = viewers.get(&question.id.0).cloned();
+        }
+        Ok(())
+    }
+
+    /// Fill in how the account relates to each of the answers
+    pub async fn set_answer_viewers(
+        &self,
+        answers: &mut [Answer],
+        account_id: &AccountId,
+    ) -> Result<(), Error> {
+        let ids: Vec<i32> = answers.iter().map(|answer| answer.id.0).collect();
+
+        let viewers: HashMap<i32, AnswerViewer> = match sqlx::query(
+            "SELECT a.id, v.value AS vote, a.account_id IS NOT DISTINCT FROM $2 AS is_author
+            FROM answers a
+            LEFT JOIN answer_votes v ON v.answer_id = a.id AND v.account_id = $2
+            WHERE a.id = ANY($1)",
+        )
+        .bind(ids)
+        .bind(account_id.0)
+        .map(|row: PgRow| {
+            (
+                row.get("id"),
+                AnswerViewer {
+                    vote: row.get::<Option<i16>, _>("vote").map(VoteValue::from_db),
+                    is_author: row.get("is_author"),
+                },
+            )
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(viewers) => viewers.into_iter().collect(),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        for answer in answers.iter_mut() {
+            answer.viewer = viewers.get(&answer.id.0).cloned();
+        }
+        Ok(())
+    }
+
+    /// Add or remove the question from the bookmarks of the account
+    pub async fn bookmark_question(
+        &self,
+        question_id: i32,
+        account_id: &AccountId,
+        bookmarked: bool,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "SELECT id FROM questions
+            WHERE id = $1 AND deleted_at IS NULL AND moderation_state = 'visible'",
+        )
+        .bind(question_id)
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(Error::QuestionNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        let query = if bookmarked {
+            "INSERT INTO question_bookmarks (question_id, account_id) VALUES ($1, $2)
+            ON CONFLICT (question_id, account_id) DO NOTHING"
+        } else {
+            "DELETE FROM question_bookmarks WHERE question_id = $1 AND account_id = $2"
+        };
+
+        match sqlx::query(query)
+            .bind(question_id)
+            .bind(account_id.0)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Snippets are HTML, the content is escaped so only their `<mark>` tags are markup
+    pub async fn search(
+        &self,
+        query: &str,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<SearchResult>, Error> {
+        match sqlx::query(
+            "SELECT * FROM (
+                SELECT 'question' AS kind, q.id, q.id AS question_id, q.title,
+                    ts_headline('english',
+                        replace(replace(replace(q.title || ' ' || q.content,
+                            '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                        query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2') AS snippet,
+                    ts_rank(q.search_vector, query) AS rank
+                FROM questions q, websearch_to_tsquery('english', $1) query
+                WHERE q.search_vector @@ query
+                    AND q.moderation_state = 'visible' AND q.deleted_at IS NULL
+                UNION ALL
+                SELECT 'answer' AS kind, a.id, a.question_id, q.title,
+                    ts_headline('english',
+                        replace(replace(replace(a.content,
+                            '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                        query, 'StartSel=<mark>, StopSel=</mark>, MaxFragments=2') AS snippet,
+                    ts_rank(a.search_vector, query) AS rank
+                FROM answers a JOIN questions q ON q.id = a.question_id,
+                    websearch_to_tsquery('english', $1) query
+                WHERE a.search_vector @@ query
+                    AND a.moderation_state = 'visible' AND a.deleted_at IS NULL
+                    AND q.moderation_state = 'visible' AND q.deleted_at IS NULL
+            ) results
+            ORDER BY rank DESC, kind DESC, id LIMIT $2 OFFSET $3",
+        )
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .map(|row: PgRow| SearchResult {
+            kind: match row.get::<&str, _>("kind") {
+                "question" => SearchResultKind::Question,
+                _ => SearchResultKind::Answer,
+            },
+            id: row.get("id"),
+            question_id: QuestionId(row.get("question_id")),
+            title: row.get("title"),
+            snippet: row.get("snippet"),
+            rank: row.get("rank"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(results) => Ok(results),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Flag visible content, queueing it for moderation once it has `threshold` open flags.
+    /// Returns the state the content is in afterwards.
+    pub async fn flag_content(
+        &self,
+        kind: ContentKind,
+        target_id: i32,
+        account_id: &AccountId,
+        reason: &str,
+        threshold: FlagThreshold,
+    ) -> Result<ModerationState, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        match sqlx::query(&format!(
+            "SELECT id FROM {} WHERE id = $1 AND moderation_state = 'visible'
+            AND deleted_at IS NULL FOR UPDATE",
+            kind.table()
+        ))
+        .bind(target_id)
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(kind.not_found()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        // The count doesn't see the flag inserted by the same statement yet
+        let open_flags = match sqlx::query(
+            "WITH added AS (
+                INSERT INTO flags (target_kind, target_id, account_id, reason)
+                VALUES ($1, $2, $3, $4)
+            )
+            SELECT COUNT(*) + 1 AS open_flags FROM flags
+            WHERE target_kind = $1 AND target_id = $2 AND resolved_on IS NULL",
+        )
+        .bind(kind.as_str())
+        .bind(target_id)
+        .bind(account_id.0)
+        .bind(reason.trim())
+        .map(|row: PgRow| row.get::<i64, _>("open_flags"))
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(open_flags) => open_flags,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let state = if open_flags >= threshold.0 {
+            if let Err(e) = sqlx::query(&format!(
+                "UPDATE {} SET moderation_state = 'queued' WHERE id = $1",
+                kind.table()
+            ))
+            .bind(target_id)
+            .execute(&mut tx)
+            .await
+            {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+            ModerationState::Queued
+        } else {
+            ModerationState::Visible
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(state)
+    }
+
+    /// Queued questions and answers with their open flags, the longest waiting first
+    pub async fn get_moderation_queue(
+        &self,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<QueueItem>, Error> {
+        let mut items = match sqlx::query(
+            "SELECT * FROM (
+                SELECT 'question' AS kind, q.id, q.id AS question_id, q.title, q.content,
+                    q.account_id
+                FROM questions q WHERE q.moderation_state = 'queued' AND q.deleted_at IS NULL
+                UNION ALL
+                SELECT 'answer' AS kind, a.id, a.question_id, q.title, a.content, a.account_id
+                FROM answers a JOIN questions q ON q.id = a.question_id
+                WHERE a.moderation_state = 'queued' AND a.deleted_at IS NULL
+                    AND q.deleted_at IS NULL
+            ) queue
+            ORDER BY (
+                SELECT MIN(f.created_on) FROM flags f
+                WHERE f.target_kind = queue.kind AND f.target_id = queue.id
+                    AND f.resolved_on IS NULL
+            ) NULLS LAST, kind DESC, id
+            LIMIT $1 OFFSET $2",
+        )
+        .bind(limit.map(i64::from))
+        .bind(i64::from(offset))
+        .map(|row: PgRow| QueueItem {
+            kind: ContentKind::from_db(row.get("kind")),
+            id: row.get("id"),
+            question_id: QuestionId(row.get("question_id")),
+            title: row.get("title"),
+            content: row.get("content"),
+            account_id: row.get::<Option<i32>, _>("account_id").map(AccountId),
+            flags: Vec::new(),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(items) => items,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let kinds: Vec<&str> = items.iter().map(|item| item.kind.as_str()).collect();
+        let ids: Vec<i32> = items.iter().map(|item| item.id).collect();
+        let flags = match sqlx::query(
+            "SELECT f.* FROM flags f JOIN UNNEST($1::text[], $2::integer[]) AS t(kind, id)
+                ON f.target_kind = t.kind AND f.target_id = t.id
+            WHERE f.resolved_on IS NULL
+            ORDER BY f.created_on",
+        )
+        .bind(kinds)
+        .bind(ids)
+        .map(|row: PgRow| flag(&row))
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(flags) => flags,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let mut by_target: HashMap<(&str, i32), Vec<Flag>> = HashMap::new();
+        for flag in flags {
+            by_target
+                .entry((flag.kind.as_str(), flag.target_id))
+                .or_default()
+                .push(flag);
+        }
+        for item in items.iter_mut() {
+            item.flags = by_target
+                .remove(&(item.kind.as_str(), item.id))
+                .unwrap_or_default();
+        }
+
+        Ok(items)
+    }
+
+    /// Apply the decision of a moderator, resolve the open flags of the content and record
+    /// the action in the audit log
+    pub async fn moderate(
+        &self,
+        kind: ContentKind,
+        target_id: i32,
+        action: ModerationAction,
+        moderator_id: &AccountId,
+        note: Option<String>,
+    ) -> Result<ModerationRecord, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let state = match sqlx::query(&format!(
+            "SELECT moderation_state FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+            kind.table()
+        ))
+        .bind(target_id)
+        .map(|row: PgRow| ModerationState::from_db(row.get("moderation_state")))
+        .fetch_optional(&mut tx)
+        .await
+        {
+            Ok(Some(state)) => state,
+            Ok(None) => return Err(kind.not_found()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+        let new_state = action.apply(state).ok_or(Error::InvalidModerationAction)?;
+
+        if let Err(e) = sqlx::query(&format!(
+            "UPDATE {} SET moderation_state = $1 WHERE id = $2",
+            kind.table()
+        ))
+        .bind(new_state.as_str())
+        .bind(target_id)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        if let Err(e) = sqlx::query(
+            "UPDATE flags SET resolved_on = NOW()
+            WHERE target_kind = $1 AND target_id = $2 AND resolved_on IS NULL",
+        )
+        .bind(kind.as_str())
+        .bind(target_id)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        let record = match sqlx::query(
+            "INSERT INTO moderation_actions (moderator_id, action, target_kind, target_id, note)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *",
+        )
+        .bind(moderator_id.0)
+        .bind(action.as_str())
+        .bind(kind.as_str())
+        .bind(target_id)
+        .bind(note)
+        .map(|row: PgRow| ModerationRecord {
+            id: row.get("id"),
+            moderator_id: AccountId(row.get("moderator_id")),
+            action: ModerationAction::from_db(row.get("action")),
+            kind: ContentKind::from_db(row.get("target_kind")),
+            target_id: row.get("target_id"),
+            note: row.get("note"),
+            created_on: row.get("created_on"),
+        })
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(record) => record,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(record)
+    }
+
+    /// Deletion of a soft deleted question or answer, `None` when it isn't deleted
+    pub async fn get_deletion(
+        &self,
+        kind: ContentKind,
+        target_id: i32,
+    ) -> Result<Option<Deletion>, Error> {
+        let deletion = match sqlx::query(&format!(
+            "SELECT deleted_at, deleted_by, account_id FROM {} WHERE id = $1",
+            kind.table()
+        ))
+        .bind(target_id)
+        .map(|row: PgRow| {
+            row.get::<Option<DateTime<Utc>>, _>("deleted_at")
+                .map(|deleted_at| Deletion {
+                    deleted_at,
+                    deleted_by: row.get::<Option<i32>, _>("deleted_by").map(AccountId),
+                    account_id: row.get::<Option<i32>, _>("account_id").map(AccountId),
+                })
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(deletion)) => deletion,
+            Ok(None) => return Err(kind.not_found()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+        Ok(deletion)
+    }
+
+    /// Returns `false` when the content isn't deleted (anymore)
+    pub async fn undelete(&self, kind: ContentKind, target_id: i32) -> Result<bool, Error> {
+        match sqlx::query(&format!(
+            "UPDATE {} SET deleted_at = NULL, deleted_by = NULL
+            WHERE id = $1 AND deleted_at IS NOT NULL",
+            kind.table()
+        ))
+        .bind(target_id)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Delete for good what was soft deleted before `cutoff`, along with the answers of
+    /// those questions. Returns the number of questions and answers removed.
+    pub async fn purge_deleted(&self, cutoff: DateTime<Utc>) -> Result<(u64, u64), Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let purged_questions =
+            match sqlx::query("SELECT id FROM questions WHERE deleted_at < $1 FOR UPDATE")
+                .bind(cutoff)
+                .map(|row: PgRow| row.get::<i32, _>("id"))
+                .fetch_all(&mut tx)
+                .await
+            {
+                Ok(ids) => ids,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            };
+
+        let purged_answers = match sqlx::query(
+            "DELETE FROM answers WHERE deleted_at < $1 OR question_id = ANY($2) RETURNING id",
+        )
+        .bind(cutoff)
+        .bind(&purged_questions)
+        .map(|row: PgRow| row.get::<i32, _>("id"))
+        .fetch_all(&mut tx)
+        .await
+        {
+            Ok(ids) => ids,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        if let Err(e) = sqlx::query("DELETE FROM questions WHERE id = ANY($1)")
+            .bind(&purged_questions)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        // Flags point at their content without a foreign key
+        for (kind, ids) in [
+            (ContentKind::Question, &purged_questions),
+            (ContentKind::Answer, &purged_answers),
+        ] {
+            if let Err(e) =
+                sqlx::query("DELETE FROM flags WHERE target_kind = $1 AND target_id = ANY($2)")
+                    .bind(kind.as_str())
+                    .bind(ids)
+                    .execute(&mut tx)
+                    .await
+            {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok((purged_questions.len() as u64, purged_answers.len() as u64))
+    }
+
+    pub async fn add_account(self, account: Account) -> Result<AccountId, Error> {
+        match sqlx::query("INSERT INTO accounts (email, password) VALUES ($1, $2) RETURNING id")
+            .bind(account.email)
+            .bind(account.password)
+            .map(|row: PgRow| AccountId(row.get("id")))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account_id) => Ok(account_id),
+            Err(error) => {
+                tracing::event!(
+                    tracing::Level::ERROR,
+                    code = error
+                        .as_database_error()
+                        .unwrap()
+                        .code()
+                        .unwrap()
+                        .parse::<i32>()
+                        .unwrap(),
+                    db_message = error.as_database_error().unwrap().message(),
+                    constraint = error.as_database_error().unwrap().constraint().unwrap(),
+                );
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    pub async fn get_account(&self, email: String) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where email = $1")
+            .bind(email)
+            .map(|row: PgRow| Account {
+                id: Some(AccountId(row.get("id"))),
+                email: row.get("email"),
+                password: row.get::<Option<String>, _>("password").unwrap_or_default(),
+            })
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => {
+                println!("Got account!");
+                Ok(account)
+            }
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    pub async fn get_account_by_id(&self, account_id: &AccountId) -> Result<Account, Error> {
+        match sqlx::query("SELECT * from accounts where id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| Account {
+                id: Some(AccountId(row.get("id"))),
+                email: row.get("email"),
+                password: row.get::<Option<String>, _>("password").unwrap_or_default(),
+            })
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account),
+            Err(error) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", error);
+                Err(Error::DatabaseQueryError(error))
+            }
+        }
+    }
+
+    /// Set a new password and log out every other session of the account, all at once.
+    /// Only the session with the id `keep` stays active, tokens issued without a session id
+    /// are revoked as well. API keys stay valid, unlike with `reset_password`.
+    pub async fn change_password(
+        &self,
+        account_id: &AccountId,
+        hashed_password: String,
+        keep: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        if let Err(e) = sqlx::query(
+            "UPDATE accounts SET password = $1, sessions_revoked_before = NOW() WHERE id = $2",
+        )
+        .bind(hashed_password)
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        if let Err(e) = sqlx::query(
+            "UPDATE sessions SET revoked_on = NOW()
+            WHERE account_id = $1 AND revoked_on IS NULL AND id IS DISTINCT FROM $2",
+        )
+        .bind(account_id.0)
+        .bind(keep)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)
+    }
+
+    pub async fn add_password_reset(
+        &self,
+        id: &str,
+        account_id: &AccountId,
+        token_hash: String,
+        expires_on: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO password_resets (id, account_id, token_hash, expires_on)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .bind(token_hash)
+        .bind(expires_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_password_reset(&self, id: &str) -> Result<Option<PasswordReset>, Error> {
+        match sqlx::query("SELECT * FROM password_resets WHERE id = $1")
+            .bind(id)
+            .map(|row: PgRow| PasswordReset {
+                id: row.get("id"),
+                account_id: AccountId(row.get("account_id")),
+                token_hash: row.get("token_hash"),
+                expires_on: row.get("expires_on"),
+                used: row.get::<Option<DateTime<Utc>>, _>("used_on").is_some(),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(reset) => Ok(reset),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Use the reset token to set a new password, log out every session of the account and
+    /// revoke its API keys, all at once. Returns `false` when the token was already used.
+    ///
+    /// A reset means the old password may have leaked, so keys created with it can't be
+    /// trusted either. `change_password` keeps them, its caller proved they knew the password.
+    pub async fn reset_password(
+        &self,
+        reset_id: &str,
+        account_id: &AccountId,
+        hashed_password: String,
+    ) -> Result<bool, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        match sqlx::query(
+            "UPDATE password_resets SET used_on = NOW() WHERE id = $1 AND used_on IS NULL",
+        )
+        .bind(reset_id)
+        .execute(&mut tx)
+        .await
+        {
+            Ok(res) if res.rows_affected() == 0 => return Ok(false),
+            Ok(_) => {}
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        if let Err(e) = sqlx::query("UPDATE accounts SET password = $1 WHERE id = $2")
+            .bind(hashed_password)
+            .bind(account_id.0)
+            .execute(&mut tx)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        // Whoever knew the old password must not stay logged in, nor keep using the API
+        for statement in [
+            "UPDATE sessions SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL",
+            "UPDATE accounts SET sessions_revoked_before = NOW() WHERE id = $1",
+            "UPDATE api_keys SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL",
+        ] {
+            if let Err(e) = sqlx::query(statement)
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await
+            {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(true)
+    }
+
+    pub async fn is_email_verified(&self, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query("SELECT * FROM accounts WHERE id = $1 AND email_verified_on IS NOT NULL")
+            .bind(account_id.0)
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(account) => Ok(account.is_some()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_email_verification(
+        &self,
+        id: &str,
+        account_id: &AccountId,
+        token_hash: String,
+        expires_on: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO email_verifications (id, account_id, token_hash, expires_on)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .bind(token_hash)
+        .bind(expires_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_email_verification(
+        &self,
+        id: &str,
+    ) -> Result<Option<EmailVerification>, Error> {
+        match sqlx::query("SELECT * FROM email_verifications WHERE id = $1")
+            .bind(id)
+            .map(|row: PgRow| EmailVerification {
+                id: row.get("id"),
+                account_id: AccountId(row.get("account_id")),
+                token_hash: row.get("token_hash"),
+                expires_on: row.get("expires_on"),
+                used: row.get::<Option<DateTime<Utc>>, _>("used_on").is_some(),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(verification) => Ok(verification),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Use the verification token and mark the account verified, returns `false` when the
+    /// token was already used
+    pub async fn verify_email(&self, verification: &EmailVerification) -> Result<bool, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let used = match sqlx::query(
+            "UPDATE email_verifications SET used_on = NOW() WHERE id = $1 AND used_on IS NULL",
+        )
+        .bind(&verification.id)
+        .execute(&mut tx)
+        .await
+        {
+            Ok(res) => res.rows_affected() > 0,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+        if !used {
+            return Ok(false);
+        }
+
+        if let Err(e) = sqlx::query(
+            "UPDATE accounts SET email_verified_on = COALESCE(email_verified_on, NOW())
+            WHERE id = $1",
+        )
+        .bind(verification.account_id.0)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(true)
+    }
+
+    pub async fn get_profile(&self, account_id: &AccountId) -> Result<Profile, Error> {
+        match sqlx::query("SELECT * FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| Profile {
+                id: AccountId(row.get("id")),
+                email: row.get("email"),
+                display_name: row.get("display_name"),
+                bio: row.get("bio"),
+                avatar_url: row.get("avatar_url"),
+                role: Role::from_db(row.get("role")),
+                email_verified: row
+                    .get::<Option<DateTime<Utc>>, _>("email_verified_on")
+                    .is_some(),
+                created_at: row.get("created_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(profile)) => Ok(profile),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn update_profile(
+        &self,
+        account_id: &AccountId,
+        update: ProfileUpdate,
+    ) -> Result<Profile, Error> {
+        // Absent fields keep their value, empty strings clear it
+        match sqlx::query(
+            "UPDATE accounts SET
+                display_name = CASE WHEN $1::text IS NULL THEN display_name ELSE NULLIF($1, '') END,
+                bio = CASE WHEN $2::text IS NULL THEN bio ELSE NULLIF($2, '') END,
+                avatar_url = CASE WHEN $3::text IS NULL THEN avatar_url ELSE NULLIF($3, '') END
+            WHERE id = $4",
+        )
+        .bind(update.display_name)
+        .bind(update.bio)
+        .bind(update.avatar_url)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => self.get_profile(account_id).await,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_public_profile(&self, account_id: &AccountId) -> Result<PublicProfile, Error> {
+        match sqlx::query(
+            "SELECT id, display_name, bio, avatar_url, created_on,
+                (SELECT COUNT(*) FROM questions q
+                    WHERE q.account_id = accounts.id AND q.deleted_at IS NULL) AS question_count,
+                (SELECT COUNT(*) FROM answers a
+                    WHERE a.account_id = accounts.id AND a.deleted_at IS NULL) AS answer_count
+            FROM accounts WHERE id = $1",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| PublicProfile {
+            id: AccountId(row.get("id")),
+            display_name: row.get("display_name"),
+            bio: row.get("bio"),
+            avatar_url: row.get("avatar_url"),
+            created_at: row.get("created_on"),
+            question_count: row.get("question_count"),
+            answer_count: row.get("answer_count"),
+        })
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(Some(profile)) => Ok(profile),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_account_role(&self, account_id: &AccountId) -> Result<Role, Error> {
+        match sqlx::query("SELECT role FROM accounts WHERE id = $1")
+            .bind(account_id.0)
+            .map(|row: PgRow| Role::from_db(row.get("role")))
+            .fetch_one(&self.connection)
+            .await
+        {
+            Ok(role) => Ok(role),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_accounts(
+        &self,
+        limit: Option<u32>,
+        offset: u32,
+    ) -> Result<Vec<AccountSummary>, Error> {
+        match sqlx::query("SELECT * FROM accounts ORDER BY id LIMIT $1 OFFSET $2")
+            .bind(limit)
+            .bind(offset)
+            .map(|row: PgRow| AccountSummary {
+                id: AccountId(row.get("id")),
+                email: row.get("email"),
+                role: Role::from_db(row.get("role")),
+                email_verified: row
+                    .get::<Option<DateTime<Utc>>, _>("email_verified_on")
+                    .is_some(),
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(accounts) => Ok(accounts),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn set_account_role(
+        &self,
+        account_id: &AccountId,
+        role: Role,
+    ) -> Result<AccountSummary, Error> {
+        match sqlx::query("UPDATE accounts SET role = $1 WHERE id = $2 RETURNING *")
+            .bind(role.as_str())
+            .bind(account_id.0)
+            .map(|row: PgRow| AccountSummary {
+                id: AccountId(row.get("id")),
+                email: row.get("email"),
+                role: Role::from_db(row.get("role")),
+                email_verified: row
+                    .get::<Option<DateTime<Utc>>, _>("email_verified_on")
+                    .is_some(),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(Some(account)) => Ok(account),
+            Ok(None) => Err(Error::AccountNotFound),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Remove the account and everything tied to it, keeping or deleting its content
+    pub async fn delete_account(
+        &self,
+        account_id: &AccountId,
+        policy: DeletionPolicy,
+    ) -> Result<(), Error> {
+        let content: &[&str] = match policy {
+            DeletionPolicy::Anonymize => &[
+                "UPDATE answers SET account_id = NULL WHERE account_id = $1",
+                "UPDATE questions SET account_id = NULL WHERE account_id = $1",
+            ],
+            DeletionPolicy::Cascade => &[
+                // Flags of other accounts on the content go with it, they have no foreign key
+                "DELETE FROM flags WHERE target_kind = 'answer' AND target_id IN (
+                    SELECT id FROM answers WHERE account_id = $1
+                    OR question_id IN (SELECT id FROM questions WHERE account_id = $1)
+                )",
+                "DELETE FROM flags WHERE target_kind = 'question'
+                AND target_id IN (SELECT id FROM questions WHERE account_id = $1)",
+                "DELETE FROM answers WHERE account_id = $1
+                OR question_id IN (SELECT id FROM questions WHERE account_id = $1)",
+                "DELETE FROM questions WHERE account_id = $1",
+            ],
+        };
+        // Votes are taken back from the scores of the content that is left
+        let rest = [
+            "WITH removed AS (
+                DELETE FROM question_votes WHERE account_id = $1 RETURNING question_id, value
+            )
+            UPDATE questions SET score = score - removed.value
+            FROM removed WHERE questions.id = removed.question_id",
+            "WITH removed AS (
+                DELETE FROM answer_votes WHERE account_id = $1 RETURNING answer_id, value
+            )
+            UPDATE answers SET score = score - removed.value
+            FROM removed WHERE answers.id = removed.answer_id",
+            // Edits to content that is left are kept without their editor
+            "UPDATE question_revisions SET editor_id = NULL WHERE editor_id = $1",
+            "UPDATE answer_revisions SET editor_id = NULL WHERE editor_id = $1",
+            "DELETE FROM question_bookmarks WHERE account_id = $1",
+            "DELETE FROM api_keys WHERE account_id = $1",
+            "DELETE FROM account_identities WHERE account_id = $1",
+            // Moderation actions stay in the audit log, flags are the account's own data
+            "DELETE FROM flags WHERE account_id = $1",
+            "DELETE FROM sessions WHERE account_id = $1",
+            "DELETE FROM password_resets WHERE account_id = $1",
+            "DELETE FROM email_verifications WHERE account_id = $1",
+            "DELETE FROM login_attempts
+            WHERE kind = 'email' AND key = (SELECT LOWER(email) FROM accounts WHERE id = $1)",
+            "DELETE FROM accounts WHERE id = $1",
+        ];
+
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        for statement in content.iter().chain(rest.iter()) {
+            if let Err(e) = sqlx::query(statement)
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await
+            {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)
+    }
+
+    /// Collect everything stored about the account, secrets and password hashes excluded
+    pub async fn export_account(&self, account_id: &AccountId) -> Result<AccountExport, Error> {
+        let profile = self.get_profile(account_id).await?;
+
+        let questions =
+            match sqlx::query("SELECT * FROM questions WHERE account_id = $1 ORDER BY id")
+                .bind(account_id.0)
+                .map(|row: PgRow| Question {
+                    id: QuestionId(row.get("id")),
+                    title: row.get("title"),
+                    content: row.get("content"),
+                    tags: row.get("tags"),
+                    accepted_answer_id: row
+                        .get::<Option<i32>, _>("accepted_answer_id")
+                        .map(AnswerId),
+                    score: row.get("score"),
+                    version: row.get("version"),
+                    viewer: None,
+                })
+                .fetch_all(&self.connection)
+                .await
+            {
+                Ok(questions) => questions,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            };
+
+        let answers = match sqlx::query("SELECT * FROM answers WHERE account_id = $1 ORDER BY id")
+            .bind(account_id.0)
+            .map(|row: PgRow| Answer {
+                id: AnswerId(row.get("id")),
+                content: row.get("content"),
+                question_id: QuestionId(row.get("question_id")),
+                score: row.get("score"),
+                version: row.get("version"),
+                viewer: None,
+            })
+            .fetch_all(&self.connection)
+            .await
+        {
+            Ok(answers) => answers,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let mut votes = Vec::new();
+        for query in [
+            "SELECT question_id AS target_id, value, created_on FROM question_votes
+            WHERE account_id = $1 ORDER BY created_on",
+            "SELECT answer_id AS target_id, value, created_on FROM answer_votes
+            WHERE account_id = $1 ORDER BY created_on",
+        ] {
+            match sqlx::query(query)
+                .bind(account_id.0)
+                .map(|row: PgRow| VoteRecord {
+                    target_id: row.get("target_id"),
+                    vote: VoteValue::from_db(row.get("value")),
+                    created_on: row.get("created_on"),
+                })
+                .fetch_all(&self.connection)
+                .await
+            {
+                Ok(records) => votes.push(records),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            }
+        }
+
+        let bookmarks = match sqlx::query(
+            "SELECT question_id, created_on FROM question_bookmarks
+            WHERE account_id = $1 ORDER BY created_on",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| BookmarkRecord {
+            question_id: QuestionId(row.get("question_id")),
+            created_on: row.get("created_on"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(bookmarks) => bookmarks,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let api_keys = self.get_api_keys(account_id, true).await?;
+
+        let identities = match sqlx::query(
+            "SELECT issuer, subject, created_on FROM account_identities
+            WHERE account_id = $1 ORDER BY created_on",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| IdentityRecord {
+            issuer: row.get("issuer"),
+            subject: row.get("subject"),
+            created_on: row.get("created_on"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(identities) => identities,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let flags =
+            match sqlx::query("SELECT * FROM flags WHERE account_id = $1 ORDER BY created_on")
+                .bind(account_id.0)
+                .map(|row: PgRow| flag(&row))
+                .fetch_all(&self.connection)
+                .await
+            {
+                Ok(flags) => flags,
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            };
+
+        let sessions = match sqlx::query(
+            "SELECT id, created_on, expires_on, revoked_on FROM sessions
+            WHERE account_id = $1 ORDER BY created_on",
+        )
+        .bind(account_id.0)
+        .map(|row: PgRow| SessionRecord {
+            id: row.get("id"),
+            created_on: row.get("created_on"),
+            expires_on: row.get("expires_on"),
+            revoked_on: row.get("revoked_on"),
+        })
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        let mut tokens = Vec::new();
+        for query in [
+            "SELECT id, created_on, expires_on, used_on FROM password_resets
+            WHERE account_id = $1 ORDER BY created_on",
+            "SELECT id, created_on, expires_on, used_on FROM email_verifications
+            WHERE account_id = $1 ORDER BY created_on",
+        ] {
+            match sqlx::query(query)
+                .bind(account_id.0)
+                .map(|row: PgRow| TokenRecord {
+                    id: row.get("id"),
+                    created_on: row.get("created_on"),
+                    expires_on: row.get("expires_on"),
+                    used_on: row.get("used_on"),
+                })
+                .fetch_all(&self.connection)
+                .await
+            {
+                Ok(records) => tokens.push(records),
+                Err(e) => {
+                    tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                    return Err(Error::DatabaseQueryError(e));
+                }
+            }
+        }
+
+        let email_verifications = tokens.pop().unwrap_or_default();
+        let password_resets = tokens.pop().unwrap_or_default();
+        let answer_votes = votes.pop().unwrap_or_default();
+        let question_votes = votes.pop().unwrap_or_default();
+
+        Ok(AccountExport {
+            exported_at: Utc::now(),
+            profile,
+            questions,
+            answers,
+            question_votes,
+            answer_votes,
+            bookmarks,
+            flags,
+            api_keys,
+            identities,
+            sessions,
+            password_resets,
+            email_verifications,
+        })
+    }
+
+    /// Latest lockout still running for any of the given email address or client IP
+    pub async fn get_login_lockout(
+        &self,
+        keys: &[(LoginAttemptKind, String)],
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let kinds: Vec<&str> = keys.iter().map(|(kind, _)| kind.as_str()).collect();
+        let values: Vec<&str> = keys.iter().map(|(_, key)| key.as_str()).collect();
+
+        match sqlx::query(
+            "SELECT MAX(locked_until) AS locked_until FROM login_attempts
+            WHERE (kind, key) IN (SELECT * FROM UNNEST($1::text[], $2::text[]))
+            AND locked_until > NOW()",
+        )
+        .bind(kinds)
+        .bind(values)
+        .map(|row: PgRow| row.get("locked_until"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(locked_until) => Ok(locked_until),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Count a failed login, failures before `window_start` are forgotten.
+    /// Returns the number of failures in the current window
+    pub async fn add_login_failure(
+        &self,
+        kind: LoginAttemptKind,
+        key: &str,
+        window_start: DateTime<Utc>,
+    ) -> Result<i32, Error> {
+        match sqlx::query(
+            "INSERT INTO login_attempts (kind, key, failures, last_failure_on)
+            VALUES ($1, $2, 1, NOW())
+            ON CONFLICT (kind, key) DO UPDATE SET
+                failures = CASE WHEN login_attempts.last_failure_on < $3 THEN 1
+                    ELSE login_attempts.failures + 1 END,
+                last_failure_on = NOW()
+            RETURNING failures",
+        )
+        .bind(kind.as_str())
+        .bind(key)
+        .bind(window_start)
+        .map(|row: PgRow| row.get("failures"))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(failures) => Ok(failures),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn lock_login(
+        &self,
+        kind: LoginAttemptKind,
+        key: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "UPDATE login_attempts SET locked_until = $3 WHERE kind = $1 AND key = $2",
+        )
+        .bind(kind.as_str())
+        .bind(key)
+        .bind(locked_until)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn clear_login_failures(
+        &self,
+        kind: LoginAttemptKind,
+        key: &str,
+    ) -> Result<(), Error> {
+        match sqlx::query("DELETE FROM login_attempts WHERE kind = $1 AND key = $2")
+            .bind(kind.as_str())
+            .bind(key)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_api_key(
+        &self,
+        id: &str,
+        account_id: &AccountId,
+        new_key: &NewApiKey,
+        key_hash: String,
+    ) -> Result<ApiKeySummary, Error> {
+        let scopes: Vec<&str> = new_key.scopes.iter().map(ApiKeyScope::as_str).collect();
+
+        match sqlx::query(
+            "INSERT INTO api_keys (id, account_id, name, scopes, key_hash)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .bind(new_key.name.trim())
+        .bind(scopes)
+        .bind(key_hash)
+        .map(|row: PgRow| api_key_summary(&row))
+        .fetch_one(&self.connection)
+        .await
+        {
+            Ok(api_key) => Ok(api_key),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_api_key(&self, id: &str) -> Result<Option<ApiKey>, Error> {
+        match sqlx::query("SELECT * FROM api_keys WHERE id = $1")
+            .bind(id)
+            .map(|row: PgRow| ApiKey {
+                id: row.get("id"),
+                account_id: AccountId(row.get("account_id")),
+                scopes: api_key_scopes(&row),
+                key_hash: row.get("key_hash"),
+                created_on: row.get("created_on"),
+                revoked: row.get::<Option<DateTime<Utc>>, _>("revoked_on").is_some(),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(api_key) => Ok(api_key),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// API keys of the account, revoked ones only when `include_revoked` is set
+    pub async fn get_api_keys(
+        &self,
+        account_id: &AccountId,
+        include_revoked: bool,
+    ) -> Result<Vec<ApiKeySummary>, Error> {
+        match sqlx::query(
+            "SELECT * FROM api_keys WHERE account_id = $1 AND ($2 OR revoked_on IS NULL)
+            ORDER BY created_on",
+        )
+        .bind(account_id.0)
+        .bind(include_revoked)
+        .map(|row: PgRow| api_key_summary(&row))
+        .fetch_all(&self.connection)
+        .await
+        {
+            Ok(api_keys) => Ok(api_keys),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns `false` when the account has no such API key still active
+    pub async fn revoke_api_key(&self, id: &str, account_id: &AccountId) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE api_keys SET revoked_on = NOW()
+            WHERE id = $1 AND account_id = $2 AND revoked_on IS NULL",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn touch_api_key(&self, id: &str) -> Result<(), Error> {
+        match sqlx::query("UPDATE api_keys SET last_used_on = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.connection)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Remember a login sent to the identity provider, dropping the ones that expired
+    pub async fn add_oidc_login(&self, login: &PendingOidcLogin) -> Result<(), Error> {
+        if let Err(e) = sqlx::query("DELETE FROM oidc_logins WHERE expires_on < NOW()")
+            .execute(&self.connection)
+            .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        match sqlx::query(
+            "INSERT INTO oidc_logins (state, nonce, code_verifier, expires_on)
+            VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&login.state)
+        .bind(&login.nonce)
+        .bind(&login.code_verifier)
+        .bind(login.expires_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Remove and return the login started with `state`, so every callback is only accepted once
+    pub async fn take_oidc_login(&self, state: &str) -> Result<Option<PendingOidcLogin>, Error> {
+        match sqlx::query("DELETE FROM oidc_logins WHERE state = $1 RETURNING *")
+            .bind(state)
+            .map(|row: PgRow| PendingOidcLogin {
+                state: row.get("state"),
+                nonce: row.get("nonce"),
+                code_verifier: row.get("code_verifier"),
+                expires_on: row.get("expires_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(login) => Ok(login),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Account the identity provider account is linked to
+    pub async fn get_identity_account(
+        &self,
+        identity: &ExternalIdentity,
+    ) -> Result<Option<AccountId>, Error> {
+        match sqlx::query(
+            "SELECT account_id FROM account_identities WHERE issuer = $1 AND subject = $2",
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .map(|row: PgRow| AccountId(row.get("account_id")))
+        .fetch_optional(&self.connection)
+        .await
+        {
+            Ok(account_id) => Ok(account_id),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn add_identity(
+        &self,
+        account_id: &AccountId,
+        identity: &ExternalIdentity,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO account_identities (issuer, subject, account_id) VALUES ($1, $2, $3)",
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(account_id.0)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Create an account without a password for the identity, its email address was already
+    /// verified by the identity provider
+    pub async fn add_identity_account(
+        &self,
+        email: &str,
+        identity: &ExternalIdentity,
+    ) -> Result<AccountId, Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        let account_id = match sqlx::query(
+            "INSERT INTO accounts (email, password, email_verified_on) VALUES ($1, NULL, NOW())
+            RETURNING id",
+        )
+        .bind(email)
+        .map(|row: PgRow| AccountId(row.get("id")))
+        .fetch_one(&mut tx)
+        .await
+        {
+            Ok(account_id) => account_id,
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                return Err(Error::DatabaseQueryError(e));
+            }
+        };
+
+        if let Err(e) = sqlx::query(
+            "INSERT INTO account_identities (issuer, subject, account_id) VALUES ($1, $2, $3)",
+        )
+        .bind(&identity.issuer)
+        .bind(&identity.subject)
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)?;
+        Ok(account_id)
+    }
+
+    pub async fn add_session(
+        &self,
+        id: &str,
+        account_id: &AccountId,
+        refresh_token_hash: String,
+        expires_on: DateTime<Utc>,
+        auth_method: AuthMethod,
+        authenticated_on: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        match sqlx::query(
+            "INSERT INTO sessions
+                (id, account_id, refresh_token_hash, expires_on, auth_method, authenticated_on)
+            VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(id)
+        .bind(account_id.0)
+        .bind(refresh_token_hash)
+        .bind(expires_on)
+        .bind(auth_method.as_str())
+        .bind(authenticated_on)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    pub async fn get_session(&self, id: &str) -> Result<Option<StoredSession>, Error> {
+        match sqlx::query("SELECT * FROM sessions WHERE id = $1")
+            .bind(id)
+            .map(|row: PgRow| StoredSession {
+                id: row.get("id"),
+                account_id: AccountId(row.get("account_id")),
+                refresh_token_hash: row.get("refresh_token_hash"),
+                expires_on: row.get("expires_on"),
+                revoked: row.get::<Option<DateTime<Utc>>, _>("revoked_on").is_some(),
+                auth_method: AuthMethod::from_db(row.get("auth_method")),
+                authenticated_on: row.get("authenticated_on"),
+            })
+            .fetch_optional(&self.connection)
+            .await
+        {
+            Ok(session) => Ok(session),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Returns `false` when the session was already revoked
+    pub async fn revoke_session(&self, id: &str) -> Result<bool, Error> {
+        match sqlx::query(
+            "UPDATE sessions SET revoked_on = NOW() WHERE id = $1 AND revoked_on IS NULL",
+        )
+        .bind(id)
+        .execute(&self.connection)
+        .await
+        {
+            Ok(res) => Ok(res.rows_affected() > 0),
+            Err(e) => {
+                tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                Err(Error::DatabaseQueryError(e))
+            }
+        }
+    }
+
+    /// Revoke every session of the account, including tokens issued without a session id
+    pub async fn revoke_all_sessions(&self, account_id: &AccountId) -> Result<(), Error> {
+        let mut tx = self
+            .connection
+            .begin()
+            .await
+            .map_err(Error::DatabaseQueryError)?;
+
+        if let Err(e) = sqlx::query(
+            "UPDATE sessions SET revoked_on = NOW() WHERE account_id = $1 AND revoked_on IS NULL",
+        )
+        .bind(account_id.0)
+        .execute(&mut tx)
+        .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        if let Err(e) =
+            sqlx::query("UPDATE accounts SET sessions_revoked_before = NOW() WHERE id = $1")
+                .bind(account_id.0)
+                .execute(&mut tx)
+                .await
+        {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            return Err(Error::DatabaseQueryError(e));
+        }
+
+        tx.commit().await.map_err(Error::DatabaseQueryError)
+    }
+
+    /// Check a decrypted token against the server-side session state
+    pub async fn is_session_active(&self, session: &Session) -> Result<bool, Error> {
+        match &session.jti {
+            Some(jti) => Ok(self
+                .get_session(jti)
+                .await?
+                .map(|stored| !stored.revoked && stored.account_id == session.account_id)
+                .unwrap_or(false)),
+            None => {
+                match sqlx::query("SELECT sessions_revoked_before FROM accounts WHERE id = $1")
+                    .bind(session.account_id.0)
+                    .map(|row: PgRow| {
+                        row.get::<Option<DateTime<Utc>>, _>("sessions_revoked_before")
+                    })
+                    .fetch_optional(&self.connection)
+                    .await
+                {
+                    Ok(Some(Some(revoked_before))) => Ok(session.nbf > revoked_before),
+                    Ok(Some(None)) => Ok(true),
+                    Ok(None) => Ok(false),
+                    Err(e) => {
+                        tracing::event!(tracing::Level::ERROR, "{:?}", e);
+                        Err(Error::DatabaseQueryError(e))
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn api_key_scopes(row: &PgRow) -> Vec<ApiKeyScope> {
+    row.get::<Vec<String>, _>("scopes")
+        .iter()
+        .filter_map(|scope| ApiKeyScope::from_db(scope))
+        .collect()
+}
+
+fn api_key_summary(row: &PgRow) -> ApiKeySummary {
+    ApiKeySummary {
+        id: row.get("id"),
+        name: row.get("name"),
+        scopes: api_key_scopes(row),
+        created_at: row.get("created_on"),
+        last_used_at: row.get("last_used_on"),
+        revoked_at: row.get("revoked_on"),
+    }
+}
+
+/// Lock the question or answer for the rest of the transaction and make sure it is still in
+/// a version `if_match` accepts
+async fn lock_version(
+    tx: &mut Transaction<'_, Postgres>,
+    kind: ContentKind,
+    target_id: i32,
+    if_match: &IfMatch,
+) -> Result<(), Error> {
+    match sqlx::query(&format!(
+        "SELECT version FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        kind.table()
+    ))
+    .bind(target_id)
+    .map(|row: PgRow| row.get::<i32, _>("version"))
+    .fetch_optional(&mut *tx)
+    .await
+    {
+        Ok(Some(version)) => if_match.check(version),
+        Ok(None) => Err(kind.not_found()),
+        Err(e) => {
+            tracing::event!(tracing::Level::ERROR, "{:?}", e);
+            Err(Error::DatabaseQueryError(e))
+        }
+    }
+}
+
+fn flag(row: &PgRow) -> Flag {
+    Flag {
+        kind: ContentKind::from_db(row.get("target_kind")),
+        target_id: row.get("target_id"),
+        account_id: AccountId(row.get("account_id")),
+        reason: row.get("reason"),
+        created_on: row.get("created_on"),
+        resolved_on: row.get("resolved_on"),
+    }
 }
@@ -1,19 +1,365 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 
+use crate::types::answer::Answer;
+use crate::types::api_key::{ApiKeyScope, ApiKeySummary};
+use crate::types::moderation::Flag;
+use crate::types::oidc::IdentityRecord;
+use crate::types::question::{Question, QuestionId};
+use crate::types::vote::VoteValue;
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct AccountId(pub i32);
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+/// Credentials of an account, only ever deserialized so the password hash can't leak in a reply.
+/// Accounts created through an identity provider are read with an empty password hash.
+#[derive(Deserialize, Clone)]
 pub struct Account {
     pub id: Option<AccountId>,
     pub email: String,
     pub password: String,
 }
 
+impl std::fmt::Debug for Account {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Account")
+            .field("id", &self.id)
+            .field("email", &self.email)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Profile of the logged in account, as returned by `/me`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub id: AccountId,
+    pub email: String,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub role: Role,
+    pub email_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Profile of an account as anyone can see it, without the email address
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublicProfile {
+    pub id: AccountId,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub question_count: i64,
+    pub answer_count: i64,
+}
+
+/// Changes to a profile, absent fields are left untouched and empty ones are cleared
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+impl ProfileUpdate {
+    pub fn validate(&self) -> Result<(), handle_errors::Error> {
+        let invalid = |name: &str| Err(handle_errors::Error::InvalidParameter(name.to_string()));
+
+        if matches!(&self.display_name, Some(name) if name.chars().count() > 50) {
+            return invalid("display_name");
+        }
+        if matches!(&self.bio, Some(bio) if bio.chars().count() > 1000) {
+            return invalid("bio");
+        }
+        if let Some(url) = &self.avatar_url {
+            let is_http = url.starts_with("https://") || url.starts_with("http://");
+            if !url.is_empty() && (!is_http || url.len() > 2048) {
+                return invalid("avatar_url");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Role of an account, every role has the permissions of the roles before it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    #[default]
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    /// Value stored in the `role` column of `accounts`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn from_db(role: &str) -> Role {
+        match role {
+            "admin" => Role::Admin,
+            "moderator" => Role::Moderator,
+            _ => Role::User,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Session {
     pub exp: DateTime<Utc>,
     pub account_id: AccountId,
     pub nbf: DateTime<Utc>,
+    /// Id of the server-side session, absent from tokens issued before sessions existed
+    #[serde(default)]
+    pub jti: Option<String>,
+    /// Tokens issued before roles existed are treated as plain users
+    #[serde(default)]
+    pub role: Role,
+    /// Set when the request was authenticated with an API key, which can only do what
+    /// its scopes allow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<Vec<ApiKeyScope>>,
+}
+
+impl Session {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
+
+    /// Sessions started by logging in have every scope
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        match &self.scopes {
+            Some(scopes) => scopes.contains(&scope),
+            None => true,
+        }
+    }
+}
+
+/// Account as listed to admins, without its password hash
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountSummary {
+    pub id: AccountId,
+    pub email: String,
+    pub role: Role,
+    pub email_verified: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleChange {
+    pub role: Role,
+}
+
+/// How the login that started a session was confirmed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMethod {
+    Password,
+    Oidc,
+}
+
+impl AuthMethod {
+    /// Value stored in the `auth_method` column of `sessions`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthMethod::Password => "password",
+            AuthMethod::Oidc => "oidc",
+        }
+    }
+
+    pub fn from_db(method: &str) -> AuthMethod {
+        match method {
+            "oidc" => AuthMethod::Oidc,
+            _ => AuthMethod::Password,
+        }
+    }
+}
+
+/// Server-side record of a login, referenced by the `jti` claim of its tokens
+#[derive(Debug, Clone)]
+pub struct StoredSession {
+    pub id: String,
+    pub account_id: AccountId,
+    pub refresh_token_hash: String,
+    pub expires_on: DateTime<Utc>,
+    pub revoked: bool,
+    pub auth_method: AuthMethod,
+    /// When the login happened, sessions started by a refresh carry over the original time
+    pub authenticated_on: DateTime<Utc>,
+}
+
+/// Tokens handed out on login and refresh
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthTokens {
+    pub token: String,
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasswordResetConfirm {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PasswordChange {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+/// Single-use password reset token, only the hash of its secret is stored
+#[derive(Debug, Clone)]
+pub struct PasswordReset {
+    pub id: String,
+    pub account_id: AccountId,
+    pub token_hash: String,
+    pub expires_on: DateTime<Utc>,
+    pub used: bool,
+}
+
+/// Single-use email verification token, only the hash of its secret is stored
+#[derive(Debug, Clone)]
+pub struct EmailVerification {
+    pub id: String,
+    pub account_id: AccountId,
+    pub token_hash: String,
+    pub expires_on: DateTime<Utc>,
+    pub used: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VerifyEmail {
+    pub token: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResendVerification {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountDeletion {
+    /// Not needed by accounts without a password, which have to have logged in recently
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// What happens to the questions and answers of a deleted account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletionPolicy {
+    /// Keep the content but drop its link to the account
+    Anonymize,
+    /// Delete the content together with the account
+    Cascade,
+}
+
+impl DeletionPolicy {
+    /// Read from `ACCOUNT_DELETION_POLICY`, anonymizing unless set to `cascade`
+    pub fn from_env() -> DeletionPolicy {
+        match std::env::var("ACCOUNT_DELETION_POLICY").as_deref() {
+            Ok("cascade") => DeletionPolicy::Cascade,
+            _ => DeletionPolicy::Anonymize,
+        }
+    }
+}
+
+/// Everything stored about an account, as returned by `/me/export`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AccountExport {
+    pub exported_at: DateTime<Utc>,
+    pub profile: Profile,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Answer>,
+    pub question_votes: Vec<VoteRecord>,
+    pub answer_votes: Vec<VoteRecord>,
+    pub bookmarks: Vec<BookmarkRecord>,
+    pub flags: Vec<Flag>,
+    pub api_keys: Vec<ApiKeySummary>,
+    pub identities: Vec<IdentityRecord>,
+    pub sessions: Vec<SessionRecord>,
+    pub password_resets: Vec<TokenRecord>,
+    pub email_verifications: Vec<TokenRecord>,
+}
+
+/// Vote cast on the question or answer with the id `target_id`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct VoteRecord {
+    pub target_id: i32,
+    pub vote: VoteValue,
+    pub created_on: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BookmarkRecord {
+    pub question_id: QuestionId,
+    pub created_on: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionRecord {
+    pub id: String,
+    pub created_on: DateTime<Utc>,
+    pub expires_on: DateTime<Utc>,
+    pub revoked_on: Option<DateTime<Utc>>,
+}
+
+/// Password reset or email verification token, without its hash
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenRecord {
+    pub id: String,
+    pub created_on: DateTime<Utc>,
+    pub expires_on: DateTime<Utc>,
+    pub used_on: Option<DateTime<Utc>>,
+}
+
+/// What failed login attempts are counted against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginAttemptKind {
+    Email,
+    Ip,
+}
+
+impl LoginAttemptKind {
+    /// Value stored in the `kind` column of `login_attempts`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LoginAttemptKind::Email => "email",
+            LoginAttemptKind::Ip => "ip",
+        }
+    }
+}
+
+/// Loose sanity check of an email address, the verification email does the real check
+pub fn is_valid_email(email: &str) -> bool {
+    if email.len() > 255 || email.chars().any(char::is_whitespace) {
+        return false;
+    }
+
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
 }
@@ -1,4 +1,5 @@
 use crate::types::question::QuestionId;
+use crate::types::vote::VoteValue;
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
@@ -9,6 +10,21 @@ pub struct Answer {
     pub id: AnswerId,
     pub question_id: QuestionId,
     pub content: String,
+    #[serde(default)]
+    pub score: i32,
+    /// Raised by every edit, sent as the `ETag` of the resource
+    #[serde(default)]
+    pub version: i32,
+    /// Only present when the request is authenticated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub viewer: Option<AnswerViewer>,
+}
+
+/// How the account reading an answer relates to it
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AnswerViewer {
+    pub vote: Option<VoteValue>,
+    pub is_author: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
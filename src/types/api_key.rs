@@ -0,0 +1,87 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::types::account::AccountId;
+use handle_errors::Error;
+
+/// What a request authenticated with an API key is allowed to do
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    Read,
+    /// Every write on the question side: asking, editing and deleting questions, accepting
+    /// answers, voting on and bookmarking questions
+    WriteQuestions,
+    /// Every write on the answer side: answering, editing and deleting answers, voting on answers
+    WriteAnswers,
+}
+
+impl ApiKeyScope {
+    /// Value stored in the `scopes` column of `api_keys`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::WriteQuestions => "write_questions",
+            ApiKeyScope::WriteAnswers => "write_answers",
+        }
+    }
+
+    pub fn from_db(scope: &str) -> Option<ApiKeyScope> {
+        match scope {
+            "read" => Some(ApiKeyScope::Read),
+            "write_questions" => Some(ApiKeyScope::WriteQuestions),
+            "write_answers" => Some(ApiKeyScope::WriteAnswers),
+            _ => None,
+        }
+    }
+}
+
+/// Stored API key, only the hash of its secret is kept
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: String,
+    pub account_id: AccountId,
+    pub scopes: Vec<ApiKeyScope>,
+    pub key_hash: String,
+    pub created_on: DateTime<Utc>,
+    pub revoked: bool,
+}
+
+/// API key as listed to its owner
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ApiKeySummary {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewApiKey {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+}
+
+impl NewApiKey {
+    pub fn validate(&self) -> Result<(), Error> {
+        let name = self.name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err(Error::InvalidParameter("name".to_string()));
+        }
+        if self.scopes.is_empty() {
+            return Err(Error::InvalidParameter("scopes".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+/// A freshly created API key, the only time its secret is shown
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKeySummary,
+    pub key: String,
+}
@@ -0,0 +1,12 @@
+use chrono::prelude::*;
+
+use crate::types::account::AccountId;
+
+/// Who soft deleted a question or answer and when, kept until the purge job removes it
+#[derive(Debug, Clone)]
+pub struct Deletion {
+    pub deleted_at: DateTime<Utc>,
+    pub deleted_by: Option<AccountId>,
+    /// Author of the content
+    pub account_id: Option<AccountId>,
+}
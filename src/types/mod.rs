@@ -1,4 +1,13 @@
 pub mod account;
 pub mod answer;
+pub mod api_key;
+pub mod deletion;
+pub mod moderation;
+pub mod oidc;
 pub mod pagination;
 pub mod question;
+pub mod revision;
+pub mod search;
+pub mod tag;
+pub mod version;
+pub mod vote;
@@ -0,0 +1,274 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::types::account::AccountId;
+use crate::types::question::QuestionId;
+use handle_errors::Error;
+
+/// What kind of content a flag or moderation action is about
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentKind {
+    Question,
+    Answer,
+}
+
+impl ContentKind {
+    /// Value stored in the `target_kind` columns
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentKind::Question => "question",
+            ContentKind::Answer => "answer",
+        }
+    }
+
+    pub fn from_db(kind: &str) -> ContentKind {
+        match kind {
+            "answer" => ContentKind::Answer,
+            _ => ContentKind::Question,
+        }
+    }
+
+    /// Table the content is stored in
+    pub fn table(&self) -> &'static str {
+        match self {
+            ContentKind::Question => "questions",
+            ContentKind::Answer => "answers",
+        }
+    }
+
+    pub fn not_found(&self) -> Error {
+        match self {
+            ContentKind::Question => Error::QuestionNotFound,
+            ContentKind::Answer => Error::AnswerNotFound,
+        }
+    }
+}
+
+/// Parsed from the `questions` or `answers` segment of moderation paths
+impl FromStr for ContentKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "questions" => Ok(ContentKind::Question),
+            "answers" => Ok(ContentKind::Answer),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Whether a question or answer is shown, stored in its `moderation_state` column
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationState {
+    Visible,
+    /// Flagged often enough to be hidden until a moderator reviews it
+    Queued,
+    Removed,
+}
+
+impl ModerationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationState::Visible => "visible",
+            ModerationState::Queued => "queued",
+            ModerationState::Removed => "removed",
+        }
+    }
+
+    pub fn from_db(state: &str) -> ModerationState {
+        match state {
+            "queued" => ModerationState::Queued,
+            "removed" => ModerationState::Removed,
+            _ => ModerationState::Visible,
+        }
+    }
+}
+
+/// Decision of a moderator about a question or answer
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    /// Show queued content again
+    Approve,
+    /// Hide content for good
+    Remove,
+    /// Show removed content again
+    Restore,
+}
+
+impl ModerationAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModerationAction::Approve => "approve",
+            ModerationAction::Remove => "remove",
+            ModerationAction::Restore => "restore",
+        }
+    }
+
+    pub fn from_db(action: &str) -> ModerationAction {
+        match action {
+            "remove" => ModerationAction::Remove,
+            "restore" => ModerationAction::Restore,
+            _ => ModerationAction::Approve,
+        }
+    }
+
+    /// State the content ends up in, `None` when the action doesn't apply to `state`
+    pub fn apply(&self, state: ModerationState) -> Option<ModerationState> {
+        match (self, state) {
+            (ModerationAction::Approve, ModerationState::Queued) => Some(ModerationState::Visible),
+            (ModerationAction::Remove, ModerationState::Visible | ModerationState::Queued) => {
+                Some(ModerationState::Removed)
+            }
+            (ModerationAction::Restore, ModerationState::Removed) => Some(ModerationState::Visible),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ModerationAction {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "approve" => Ok(ModerationAction::Approve),
+            "remove" => Ok(ModerationAction::Remove),
+            "restore" => Ok(ModerationAction::Restore),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewFlag {
+    pub reason: String,
+}
+
+impl NewFlag {
+    pub fn validate(&self) -> Result<(), Error> {
+        let reason = self.reason.trim();
+        if reason.is_empty() || reason.chars().count() > 500 {
+            return Err(Error::InvalidParameter("reason".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Flag raised by an account, resolved once a moderator acted on the content
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Flag {
+    pub kind: ContentKind,
+    pub target_id: i32,
+    pub account_id: AccountId,
+    pub reason: String,
+    pub created_on: DateTime<Utc>,
+    pub resolved_on: Option<DateTime<Utc>>,
+}
+
+/// Question or answer waiting for a moderator, with the flags that put it there
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueueItem {
+    pub kind: ContentKind,
+    pub id: i32,
+    pub question_id: QuestionId,
+    /// Title of the question, or of the question the answer belongs to
+    pub title: String,
+    pub content: String,
+    pub account_id: Option<AccountId>,
+    pub flags: Vec<Flag>,
+}
+
+/// Optional explanation a moderator attaches to an action
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ModerationNote {
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Entry of the moderation audit log
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ModerationRecord {
+    pub id: i32,
+    pub moderator_id: AccountId,
+    pub action: ModerationAction,
+    pub kind: ContentKind,
+    pub target_id: i32,
+    pub note: Option<String>,
+    pub created_on: DateTime<Utc>,
+}
+
+/// Open flags that hide a question or answer until a moderator reviews it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagThreshold(pub i64);
+
+impl FlagThreshold {
+    /// Read from `MODERATION_FLAG_THRESHOLD`, 3 unless set
+    pub fn from_env() -> FlagThreshold {
+        FlagThreshold(
+            std::env::var("MODERATION_FLAG_THRESHOLD")
+                .ok()
+                .and_then(|threshold| threshold.parse().ok())
+                .filter(|threshold| *threshold > 0)
+                .unwrap_or(3),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [ModerationState; 3] = [
+        ModerationState::Visible,
+        ModerationState::Queued,
+        ModerationState::Removed,
+    ];
+
+    fn outcomes(action: ModerationAction) -> Vec<Option<ModerationState>> {
+        STATES.iter().map(|state| action.apply(*state)).collect()
+    }
+
+    #[test]
+    fn approve_only_shows_queued_content() {
+        assert_eq!(
+            outcomes(ModerationAction::Approve),
+            vec![None, Some(ModerationState::Visible), None]
+        );
+    }
+
+    #[test]
+    fn remove_hides_content_not_removed_yet() {
+        assert_eq!(
+            outcomes(ModerationAction::Remove),
+            vec![
+                Some(ModerationState::Removed),
+                Some(ModerationState::Removed),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn restore_only_shows_removed_content() {
+        assert_eq!(
+            outcomes(ModerationAction::Restore),
+            vec![None, None, Some(ModerationState::Visible)]
+        );
+    }
+
+    #[test]
+    fn actions_round_trip_through_paths_and_the_database() {
+        for action in [
+            ModerationAction::Approve,
+            ModerationAction::Remove,
+            ModerationAction::Restore,
+        ] {
+            assert_eq!(action.as_str().parse::<ModerationAction>(), Ok(action));
+            assert_eq!(ModerationAction::from_db(action.as_str()), action);
+        }
+        assert!("delete".parse::<ModerationAction>().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Query string the identity provider redirects back to `/oidc/callback` with
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set instead of `code` when the provider turned the login down
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+/// Login started at `/oidc/login`, waiting for the provider to redirect back
+#[derive(Debug, Clone)]
+pub struct PendingOidcLogin {
+    pub state: String,
+    pub nonce: String,
+    /// PKCE secret, only its hash is sent to the provider before the code exchange
+    pub code_verifier: String,
+    pub expires_on: DateTime<Utc>,
+}
+
+/// Account at the identity provider, taken from a verified ID token
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+}
+
+/// Identity provider account linked to an account
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IdentityRecord {
+    pub issuer: String,
+    pub subject: String,
+    pub created_on: DateTime<Utc>,
+}
@@ -2,38 +2,307 @@
 
 use std::collections::HashMap;
 
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
 use handle_errors::Error;
 
+/// Biggest page size a client can ask for
+pub const MAX_LIMIT: u32 = 100;
+
 /// Pagniation struct that is getting extracted
 /// from query params
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Pagination {
     pub limit: Option<u32>,
     pub offset: u32,
+    /// Keyset cursor, only return the items sorted after this one
+    pub after: Option<Cursor>,
+}
+
+/// Position of the last item of a page, handed out to clients as an opaque string
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub id: i32,
+    pub key: CursorKey,
 }
 
-/// Extract query paramters from the `/questions` route
+/// Value of the sort column of the item the cursor points at
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "s", content = "k")]
+pub enum CursorKey {
+    Id,
+    Score(i32),
+    CreatedOn(NaiveDateTime),
+    Activity(NaiveDateTime),
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursor is always serializable");
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, Error> {
+        base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| Error::InvalidParameter("after".to_string()))
+    }
+}
+
+/// A page of a listing together with what clients need to fetch the next one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of items matching the filters across all pages
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+/// Extract query paramters from the listing routes
 /// # Example Query
-/// Get requests to this route can have a pagniation attached so we just
-/// return the question we need
-/// `/questions?start=1&send=10`
-pub fn extract_pagniation(params: HashMap<String, String>) -> Result<Pagination, Error> {
-    if params.contains_key("limit") && params.contains_key("offset") {
-        return Ok(Pagination {
-            limit: Some(
-                params
-                    .get("limit")
-                    .unwrap()
-                    .parse::<u32>()
-                    .map_err(Error::ParseError)?,
-            ),
-            offset: params
-                .get("end")
-                .unwrap()
-                .parse::<u32>()
-                .map_err(Error::ParseError)?,
-        });
-    }
-
-    Err(Error::MissingParameters)
+/// Get requests to these routes can have a pagniation attached so we just
+/// return the items we need, either by offset or by keyset cursor
+/// `/questions?limit=10&offset=20`
+/// `/questions?limit=10&after=eyJpZCI6MTIsImtleSI6eyJzIjoiSWQifX0`
+pub fn extract_pagination(params: &HashMap<String, String>) -> Result<Pagination, Error> {
+    let limit = match params.get("limit") {
+        Some(limit) => {
+            let limit = limit.parse::<u32>().map_err(Error::ParseError)?;
+            if limit == 0 || limit > MAX_LIMIT {
+                return Err(Error::RangeError);
+            }
+            Some(limit)
+        }
+        None => None,
+    };
+
+    let offset = params
+        .get("offset")
+        .map(|offset| offset.parse::<u32>().map_err(Error::ParseError))
+        .transpose()?;
+
+    let after = params.get("after").map(|c| Cursor::decode(c)).transpose()?;
+
+    if limit.is_none() && (offset.is_some() || after.is_some()) {
+        return Err(Error::MissingParameters);
+    }
+
+    if offset.is_some() && after.is_some() {
+        return Err(Error::InvalidParameter("after".to_string()));
+    }
+
+    Ok(Pagination {
+        limit,
+        offset: offset.unwrap_or(0),
+        after,
+    })
+}
+
+/// Build the RFC 8288 `Link` header value pointing to the first, previous and next pages
+///
+/// `params` are the query parameters of the current request, every one of them but the
+/// pagination ones is carried over to the generated links.
+pub fn link_header(
+    path: &str,
+    params: &[(String, String)],
+    pagination: &Pagination,
+    next_cursor: Option<&str>,
+) -> Option<String> {
+    let limit = pagination.limit?;
+    let link = |extra: &[(&str, String)], rel: &str| {
+        let mut pairs: Vec<(&str, &str)> = params
+            .iter()
+            .filter(|(k, _)| !matches!(k.as_str(), "limit" | "offset" | "after"))
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let limit = limit.to_string();
+        pairs.push(("limit", &limit));
+        pairs.extend(extra.iter().map(|(k, v)| (*k, v.as_str())));
+
+        let query = serde_urlencoded::to_string(&pairs).unwrap_or_default();
+        format!("<{}?{}>; rel=\"{}\"", path, query, rel)
+    };
+
+    let mut links = vec![link(&[], "first")];
+
+    if pagination.after.is_none() && pagination.offset > 0 {
+        let prev = pagination.offset.saturating_sub(limit);
+        links.push(link(&[("offset", prev.to_string())], "prev"));
+    }
+
+    if let Some(cursor) = next_cursor {
+        if pagination.after.is_some() {
+            links.push(link(&[("after", cursor.to_string())], "next"));
+        } else {
+            let next = pagination.offset.saturating_add(limit);
+            links.push(link(&[("offset", next.to_string())], "next"));
+        }
+    }
+
+    Some(links.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn cursors_round_trip() {
+        let created_on = NaiveDate::from_ymd_opt(2025, 5, 20)
+            .unwrap()
+            .and_hms_micro_opt(11, 0, 0, 123_456)
+            .unwrap();
+
+        for key in [
+            CursorKey::Id,
+            CursorKey::Score(-3),
+            CursorKey::CreatedOn(created_on),
+            CursorKey::Activity(created_on),
+        ] {
+            let cursor = Cursor { id: 12, key };
+            let encoded = cursor.encode();
+
+            assert!(encoded
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+            assert_eq!(Cursor::decode(&encoded).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_invalid() {
+        for cursor in ["", "not base64!", "bm90IGpzb24", "eyJpZCI6MTJ9"] {
+            assert!(
+                matches!(Cursor::decode(cursor), Err(Error::InvalidParameter(p)) if p == "after"),
+                "{} accepted",
+                cursor
+            );
+        }
+    }
+
+    #[test]
+    fn no_links_without_a_limit() {
+        assert_eq!(
+            link_header("/questions", &[], &Pagination::default(), Some("c")),
+            None
+        );
+    }
+
+    #[test]
+    fn offset_links_keep_the_filters() {
+        let pagination = Pagination {
+            limit: Some(10),
+            offset: 15,
+            after: None,
+        };
+        let params = params(&[("tag", "c#"), ("limit", "10"), ("offset", "15")]);
+
+        assert_eq!(
+            link_header("/questions", &params, &pagination, Some("c")).unwrap(),
+            "</questions?tag=c%23&limit=10>; rel=\"first\", \
+             </questions?tag=c%23&limit=10&offset=5>; rel=\"prev\", \
+             </questions?tag=c%23&limit=10&offset=25>; rel=\"next\""
+        );
+    }
+
+    #[test]
+    fn last_offset_page_has_no_next_link() {
+        let pagination = Pagination {
+            limit: Some(10),
+            offset: 0,
+            after: None,
+        };
+
+        assert_eq!(
+            link_header("/questions", &[], &pagination, None).unwrap(),
+            "</questions?limit=10>; rel=\"first\""
+        );
+    }
+
+    #[test]
+    fn next_offset_saturates() {
+        let pagination = Pagination {
+            limit: Some(MAX_LIMIT),
+            offset: u32::MAX - 1,
+            after: None,
+        };
+
+        let links = link_header("/questions", &[], &pagination, Some("c")).unwrap();
+        assert!(
+            links.ends_with(&format!(
+                "</questions?limit=100&offset={}>; rel=\"next\"",
+                u32::MAX
+            )),
+            "{}",
+            links
+        );
+    }
+
+    #[test]
+    fn cursor_pages_link_to_the_next_cursor() {
+        let cursor = Cursor {
+            id: 12,
+            key: CursorKey::Id,
+        };
+        let pagination = Pagination {
+            limit: Some(10),
+            offset: 0,
+            after: Some(cursor.clone()),
+        };
+        let params = params(&[("limit", "10"), ("after", &cursor.encode())]);
+
+        assert_eq!(
+            link_header("/questions", &params, &pagination, Some("next")).unwrap(),
+            "</questions?limit=10>; rel=\"first\", </questions?limit=10&after=next>; rel=\"next\""
+        );
+    }
+
+    #[test]
+    fn extracts_offset_or_cursor_pagination() {
+        let query = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            params(pairs).into_iter().collect()
+        };
+        let cursor = Cursor {
+            id: 12,
+            key: CursorKey::Score(4),
+        };
+
+        let pagination = extract_pagination(&query(&[("limit", "10"), ("offset", "20")])).unwrap();
+        assert_eq!((pagination.limit, pagination.offset), (Some(10), 20));
+
+        let pagination =
+            extract_pagination(&query(&[("limit", "10"), ("after", &cursor.encode())])).unwrap();
+        assert_eq!(pagination.after, Some(cursor.clone()));
+
+        assert!(matches!(
+            extract_pagination(&query(&[("offset", "20")])),
+            Err(Error::MissingParameters)
+        ));
+        assert!(matches!(
+            extract_pagination(&query(&[("limit", "0")])),
+            Err(Error::RangeError)
+        ));
+        assert!(matches!(
+            extract_pagination(&query(&[("limit", "101")])),
+            Err(Error::RangeError)
+        ));
+        assert!(matches!(
+            extract_pagination(&query(&[
+                ("limit", "10"),
+                ("offset", "20"),
+                ("after", &cursor.encode())
+            ])),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
 }
@@ -1,5 +1,10 @@
 use serde::{Deserialize, Serialize};
 
+use crate::types::account::AccountId;
+use crate::types::answer::{Answer, AnswerId};
+use crate::types::vote::VoteValue;
+use handle_errors::Error;
+
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
 pub struct QuestionId(pub i32);
 
@@ -9,6 +14,23 @@ pub struct Question {
     pub title: String,
     pub content: String,
     pub tags: Option<Vec<String>>,
+    pub accepted_answer_id: Option<AnswerId>,
+    #[serde(default)]
+    pub score: i32,
+    /// Raised by every edit, sent as the `ETag` of the resource
+    #[serde(default)]
+    pub version: i32,
+    /// Only present when the request is authenticated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub viewer: Option<QuestionViewer>,
+}
+
+/// How the account reading a question relates to it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QuestionViewer {
+    pub vote: Option<VoteValue>,
+    pub is_author: bool,
+    pub bookmarked: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,3 +39,90 @@ pub struct NewQuestion {
     pub content: String,
     pub tags: Option<Vec<String>>,
 }
+
+/// A single question together with its author and every answer posted to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionWithAnswers {
+    #[serde(flatten)]
+    pub question: Question,
+    /// Absent once the author deleted their account and the question was kept
+    pub account_id: Option<AccountId>,
+    pub answers: Vec<Answer>,
+}
+
+/// Request body used by the question owner to accept one of its answers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptAnswer {
+    pub answer_id: AnswerId,
+}
+
+/// Filters that can be applied to the `/questions` listing
+#[derive(Default, Debug)]
+pub struct QuestionFilter {
+    /// `true` only keeps questions with an accepted answer, `false` only those without
+    pub answered: Option<bool>,
+    pub sort: Option<QuestionSort>,
+    /// Only keep questions tagged with any (or all, see `match_all_tags`) of these tags
+    pub tags: Vec<String>,
+    pub match_all_tags: bool,
+}
+
+/// Ordering of the `/questions` listing, defaults to insertion order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuestionSort {
+    /// Highest score first
+    Score,
+    /// Most recently asked first
+    Newest,
+    /// Most recently asked or answered first
+    Activity,
+}
+
+/// Extract the filter query parameters from the `/questions` route
+/// # Example Query
+/// `/questions?answered=false&sort=score&tag=rust&tag=warp&tag_mode=all`
+///
+/// The parameters are taken as a list of pairs since `tag` can be repeated.
+pub fn extract_question_filter(params: &[(String, String)]) -> Result<QuestionFilter, Error> {
+    let get = |key: &str| {
+        params
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+
+    let answered = match get("answered") {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        Some(_) => return Err(Error::InvalidParameter("answered".to_string())),
+        None => None,
+    };
+
+    let sort = match get("sort") {
+        Some("score") => Some(QuestionSort::Score),
+        Some("newest") => Some(QuestionSort::Newest),
+        Some("activity") => Some(QuestionSort::Activity),
+        Some(_) => return Err(Error::InvalidParameter("sort".to_string())),
+        None => None,
+    };
+
+    let tags = params
+        .iter()
+        .filter(|(k, v)| k == "tag" && !v.is_empty())
+        .map(|(_, v)| v.clone())
+        .collect();
+
+    let match_all_tags = match get("tag_mode") {
+        Some("all") => true,
+        Some("any") | None => false,
+        Some(_) => return Err(Error::InvalidParameter("tag_mode".to_string())),
+    };
+
+    Ok(QuestionFilter {
+        answered,
+        sort,
+        tags,
+        match_all_tags,
+    })
+}
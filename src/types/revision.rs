@@ -0,0 +1,106 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+
+use crate::types::account::AccountId;
+
+/// Version of a question as it was saved by an edit
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QuestionRevision {
+    pub revision: i32,
+    /// Absent once the editor deleted their account
+    pub editor_id: Option<AccountId>,
+    pub created_on: DateTime<Utc>,
+    pub title: String,
+    pub content: String,
+    pub tags: Option<Vec<String>>,
+    /// Unified diff from the previous revision
+    pub diff: String,
+}
+
+/// Version of an answer as it was saved by an edit
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AnswerRevision {
+    pub revision: i32,
+    /// Absent once the editor deleted their account
+    pub editor_id: Option<AccountId>,
+    pub created_on: DateTime<Utc>,
+    pub content: String,
+    /// Unified diff from the previous revision
+    pub diff: String,
+}
+
+/// Text of a question revision the diffs are made of
+pub fn question_text(title: &str, content: &str, tags: Option<&[String]>) -> String {
+    format!(
+        "Title: {}\nTags: {}\n\n{}",
+        title,
+        tags.unwrap_or_default().join(", "),
+        content
+    )
+}
+
+/// Unified diff between `old`, the text of the previous revision or nothing for the
+/// first one, and `new`, the text of `revision`
+pub fn unified_diff(old: Option<&str>, new: &str, revision: i32) -> String {
+    // Texts rarely end with a line break, which would mark every last line as changed
+    let old = old.map(|old| format!("{}\n", old)).unwrap_or_default();
+    let new = format!("{}\n", new);
+
+    TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .context_radius(3)
+        .header(
+            &format!("revision {}", revision - 1),
+            &format!("revision {}", revision),
+        )
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn question_text_lists_title_tags_and_content() {
+        let tags = vec!["rust".to_string(), "warp".to_string()];
+
+        assert_eq!(
+            question_text("Title", "Line 1\nLine 2", Some(&tags)),
+            "Title: Title\nTags: rust, warp\n\nLine 1\nLine 2"
+        );
+        assert_eq!(
+            question_text("Title", "Content", None),
+            "Title: Title\nTags: \n\nContent"
+        );
+    }
+
+    #[test]
+    fn first_revision_adds_every_line() {
+        assert_eq!(
+            unified_diff(None, "one\ntwo", 1),
+            "--- revision 0\n+++ revision 1\n@@ -0,0 +1,2 @@\n+one\n+two\n"
+        );
+    }
+
+    #[test]
+    fn only_changed_lines_are_marked() {
+        assert_eq!(
+            unified_diff(Some("one\ntwo\nthree"), "one\n2\nthree", 2),
+            "--- revision 1\n+++ revision 2\n@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"
+        );
+    }
+
+    #[test]
+    fn appending_a_line_leaves_the_last_one_unchanged() {
+        let diff = unified_diff(Some("one\ntwo"), "one\ntwo\nthree", 3);
+
+        assert!(diff.contains("\n one\n two\n+three\n"), "{}", diff);
+        assert!(!diff.contains("-two"), "{}", diff);
+    }
+
+    #[test]
+    fn unchanged_text_has_an_empty_diff() {
+        assert_eq!(unified_diff(Some("same"), "same", 4), "");
+    }
+}
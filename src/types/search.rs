@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::types::question::QuestionId;
+use handle_errors::Error;
+
+/// Kind of content a search hit was found in
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchResultKind {
+    Question,
+    Answer,
+}
+
+/// A single ranked search hit, `snippet` is escaped HTML with the matched terms wrapped in `<mark>` tags
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SearchResult {
+    pub kind: SearchResultKind,
+    pub id: i32,
+    pub question_id: QuestionId,
+    pub title: String,
+    pub snippet: String,
+    pub rank: f32,
+}
+
+/// Extract the search terms from the `/search` route
+/// # Example Query
+/// `/search?q=borrow+checker&limit=10&offset=0`
+pub fn extract_search_query(params: &HashMap<String, String>) -> Result<String, Error> {
+    match params.get("q").map(|q| q.trim()) {
+        Some(q) if !q.is_empty() => Ok(q.to_string()),
+        _ => Err(Error::MissingParameters),
+    }
+}
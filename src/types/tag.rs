@@ -0,0 +1,8 @@
+use serde::{Deserialize, Serialize};
+
+/// A tag used by at least one question
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Tag {
+    pub name: String,
+    pub question_count: i64,
+}
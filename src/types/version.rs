@@ -0,0 +1,106 @@
+use warp::http::header::HeaderValue;
+
+use handle_errors::Error;
+
+/// `ETag` of a question or answer in `version`
+pub fn etag(version: i32) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{}\"", version))
+        .expect("a quoted number is a valid header value")
+}
+
+/// Versions a write is allowed to change, read from its `If-Match` header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    /// No header or `*`, the write applies to whatever version is current
+    Any,
+    Versions(Vec<i32>),
+}
+
+impl IfMatch {
+    /// Weak and malformed entity tags never match, as `If-Match` compares them strongly
+    pub fn from_header(value: Option<String>) -> IfMatch {
+        match value.as_deref().map(str::trim) {
+            None | Some("*") => IfMatch::Any,
+            Some(value) => IfMatch::Versions(
+                value
+                    .split(',')
+                    .filter_map(|tag| {
+                        tag.trim()
+                            .strip_prefix('"')?
+                            .strip_suffix('"')?
+                            .parse()
+                            .ok()
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Fails with `PreconditionFailed` unless `version` is one of the accepted versions
+    pub fn check(&self, version: i32) -> Result<(), Error> {
+        match self {
+            IfMatch::Versions(versions) if !versions.contains(&version) => {
+                Err(Error::PreconditionFailed)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_match(value: &str) -> IfMatch {
+        IfMatch::from_header(Some(value.to_string()))
+    }
+
+    #[test]
+    fn tags_are_quoted_versions() {
+        assert_eq!(etag(3), "\"3\"");
+    }
+
+    #[test]
+    fn tags_sent_with_a_read_match_on_the_next_write() {
+        let tag = etag(3).to_str().unwrap().to_string();
+
+        assert!(IfMatch::from_header(Some(tag.clone())).check(3).is_ok());
+        assert!(matches!(
+            IfMatch::from_header(Some(tag)).check(4),
+            Err(Error::PreconditionFailed)
+        ));
+    }
+
+    #[test]
+    fn missing_header_or_star_match_any_version() {
+        assert_eq!(IfMatch::from_header(None), IfMatch::Any);
+        assert_eq!(if_match(" * "), IfMatch::Any);
+        assert!(IfMatch::Any.check(42).is_ok());
+    }
+
+    #[test]
+    fn parses_lists_of_strong_tags() {
+        assert_eq!(if_match("\"1\", \"3\""), IfMatch::Versions(vec![1, 3]));
+        assert!(if_match("\"1\", \"3\"").check(3).is_ok());
+    }
+
+    #[test]
+    fn weak_and_malformed_tags_never_match() {
+        assert_eq!(
+            if_match("W/\"3\", 3, \"three\", \"4"),
+            IfMatch::Versions(vec![])
+        );
+        assert!(matches!(
+            if_match("W/\"3\"").check(3),
+            Err(Error::PreconditionFailed)
+        ));
+    }
+
+    #[test]
+    fn stale_versions_fail_the_precondition() {
+        assert!(matches!(
+            if_match("\"2\"").check(3),
+            Err(Error::PreconditionFailed)
+        ));
+    }
+}
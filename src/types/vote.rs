@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// Direction of a vote cast on a question or an answer
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteValue {
+    Up,
+    Down,
+}
+
+impl VoteValue {
+    /// Value stored in the `value` column of the votes tables
+    pub fn as_i16(self) -> i16 {
+        match self {
+            VoteValue::Up => 1,
+            VoteValue::Down => -1,
+        }
+    }
+
+    pub fn from_db(value: i16) -> VoteValue {
+        if value > 0 {
+            VoteValue::Up
+        } else {
+            VoteValue::Down
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct NewVote {
+    pub vote: VoteValue,
+}
+
+/// Aggregated score of a voted target after casting or retracting a vote
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct VoteScore {
+    pub score: i32,
+    pub vote: Option<VoteValue>,
+}